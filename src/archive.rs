@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SECONDS_PER_DAY: u32 = 86400;
+
+/// Writes `content` to `dir/YYYY/MM/DD/HHMM.<extension>` using the RIB dump time,
+/// then drops day partitions more than `retention_days` older than that dump.
+pub fn archive(
+    dir: &Path,
+    dump_time: u32,
+    extension: &str,
+    content: &[u8],
+    retention_days: Option<u32>,
+) -> io::Result<PathBuf> {
+    let day = dump_time / SECONDS_PER_DAY;
+    let (year, month, mday) = civil_from_days(day);
+    let partition = dir
+        .join(format!("{:04}", year))
+        .join(format!("{:02}", month))
+        .join(format!("{:02}", mday));
+    fs::create_dir_all(&partition)?;
+    let seconds = dump_time % SECONDS_PER_DAY;
+    let path = partition.join(format!(
+        "{:02}{:02}.{}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        extension
+    ));
+    fs::write(&path, content)?;
+
+    if let Some(retention_days) = retention_days {
+        let cutoff = civil_from_days(day.saturating_sub(retention_days));
+        prune(dir, cutoff)?;
+    }
+    Ok(path)
+}
+
+/// Removes every `YYYY/MM/DD` partition dated before `cutoff`, and any month or
+/// year directory left empty by that.
+fn prune(dir: &Path, cutoff: (u32, u32, u32)) -> io::Result<()> {
+    for year in numbered_dirs(dir)? {
+        let year_dir = dir.join(format!("{:04}", year));
+        for month in numbered_dirs(&year_dir)? {
+            let month_dir = year_dir.join(format!("{:02}", month));
+            for mday in numbered_dirs(&month_dir)? {
+                if (year, month, mday) < cutoff {
+                    fs::remove_dir_all(month_dir.join(format!("{:02}", mday)))?;
+                }
+            }
+            if fs::read_dir(&month_dir)?.next().is_none() {
+                fs::remove_dir(&month_dir)?;
+            }
+        }
+        if fs::read_dir(&year_dir)?.next().is_none() {
+            fs::remove_dir(&year_dir)?;
+        }
+    }
+    Ok(())
+}
+
+/// Subdirectories of `dir` whose names are plain numbers; anything else is left alone.
+fn numbered_dirs(dir: &Path) -> io::Result<Vec<u32>> {
+    let mut numbers = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(n) = entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+            numbers.push(n);
+        }
+    }
+    Ok(numbers)
+}
+
+/// Converts days since 1970-01-01 to a proleptic Gregorian (year, month, day).
+pub fn civil_from_days(days: u32) -> (u32, u32, u32) {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as u32;
+    (year, month, day)
+}
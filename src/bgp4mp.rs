@@ -0,0 +1,301 @@
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+
+use crate::prefix::Prefix;
+use crate::rib::Update;
+
+const MRT_BGP4MP: u16 = 16;
+const MRT_BGP4MP_ET: u16 = 17;
+const BGP4MP_MESSAGE_AS4: u16 = 4;
+const BGP_UPDATE: u8 = 2;
+
+const AFI_IPV4: u16 = 1;
+const AFI_IPV6: u16 = 2;
+const SAFI_UNICAST: u8 = 1;
+
+const ATTR_FLAG_EXTENDED_LENGTH: u8 = 0x10;
+const ATTR_AS_PATH: u8 = 2;
+const ATTR_COMMUNITY: u8 = 8;
+const ATTR_MP_REACH_NLRI: u8 = 14;
+const ATTR_MP_UNREACH_NLRI: u8 = 15;
+const AS_SEQUENCE: u8 = 2;
+
+/// Calls `f` for every UPDATE in the BGP4MP_MESSAGE_AS4 records of the MRT
+/// file at `path`. The mrt crate drops the MP_REACH_NLRI and MP_UNREACH_NLRI
+/// attributes that carry IPv6 routes, so updates are decoded here instead;
+/// unicast routes of both families are read, whichever session carried them.
+/// Other records, such as state changes or the 2-byte ASN BGP4MP_MESSAGE, are
+/// skipped.
+pub fn read_file<F: FnMut(&Update)>(path: &Path, f: &mut F) -> Result<(), String> {
+    let data = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut input = Reader(&data);
+    while !input.0.is_empty() {
+        let offset = data.len() - input.0.len();
+        read_record(&mut input, f)
+            .map_err(|e| format!("{}: record at byte {}: {}", path.display(), offset, e))?;
+    }
+    Ok(())
+}
+
+/// A cursor over network byte order data.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if self.0.len() < n {
+            return Err("truncated".to_string());
+        }
+        let (head, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn addr(&mut self, afi: u16) -> Result<IpAddr, String> {
+        match afi {
+            AFI_IPV4 => Ok(IpAddr::V4(Ipv4Addr::from(self.u32()?))),
+            AFI_IPV6 => {
+                let octets: [u8; 16] = self.take(16)?.try_into().unwrap();
+                Ok(IpAddr::V6(Ipv6Addr::from(octets)))
+            }
+            _ => Err(format!("unknown address family {}", afi)),
+        }
+    }
+}
+
+fn read_record<F: FnMut(&Update)>(input: &mut Reader, f: &mut F) -> Result<(), String> {
+    let time = input.u32()?;
+    let mrt_type = input.u16()?;
+    let subtype = input.u16()?;
+    let length = input.u32()? as usize;
+    let mut body = Reader(input.take(length)?);
+    if !matches!(mrt_type, MRT_BGP4MP | MRT_BGP4MP_ET) || subtype != BGP4MP_MESSAGE_AS4 {
+        return Ok(());
+    }
+    if mrt_type == MRT_BGP4MP_ET {
+        // Microsecond part of the timestamp.
+        body.u32()?;
+    }
+    let peer_asn = body.u32()?;
+    // Local AS and interface index.
+    body.u32()?;
+    body.u16()?;
+    let afi = body.u16()?;
+    let peer_ip = body.addr(afi)?;
+    body.addr(afi)?;
+
+    // Marker, length and type of the BGP message.
+    body.take(16)?;
+    let length = (body.u16()? as usize)
+        .checked_sub(19)
+        .ok_or("BGP message length too small")?;
+    if body.u8()? != BGP_UPDATE {
+        return Ok(());
+    }
+    let mut message = Reader(body.take(length)?);
+    let mut update = Update {
+        time,
+        peer_asn,
+        peer_ip,
+        withdrawn: Vec::new(),
+        announced: Vec::new(),
+        as_path: Vec::new(),
+        communities: Vec::new(),
+    };
+    let withdrawn_len = message.u16()? as usize;
+    read_nlri(
+        Reader(message.take(withdrawn_len)?),
+        AFI_IPV4,
+        &mut update.withdrawn,
+    )?;
+    let attributes_len = message.u16()? as usize;
+    read_attributes(Reader(message.take(attributes_len)?), &mut update)?;
+    read_nlri(message, AFI_IPV4, &mut update.announced)?;
+    f(&update);
+    Ok(())
+}
+
+/// Appends the prefixes of a withdrawn routes or NLRI field.
+fn read_nlri(mut input: Reader, afi: u16, prefixes: &mut Vec<Prefix>) -> Result<(), String> {
+    let max_len = if afi == AFI_IPV4 { 32 } else { 128 };
+    while !input.0.is_empty() {
+        let len = input.u8()?;
+        if len > max_len {
+            return Err(format!("prefix length {} out of range", len));
+        }
+        let mut octets = [0; 16];
+        let bytes = input.take((len as usize).div_ceil(8))?;
+        octets[..bytes.len()].copy_from_slice(bytes);
+        let addr = Reader(&octets).addr(afi)?;
+        prefixes.push(Prefix::new(addr, len));
+    }
+    Ok(())
+}
+
+/// Reads the AS_SEQUENCE ASNs, the communities and the unicast routes of the
+/// MP_REACH_NLRI and MP_UNREACH_NLRI attributes into `update`.
+fn read_attributes(mut input: Reader, update: &mut Update) -> Result<(), String> {
+    while !input.0.is_empty() {
+        let flags = input.u8()?;
+        let code = input.u8()?;
+        let len = if flags & ATTR_FLAG_EXTENDED_LENGTH != 0 {
+            input.u16()? as usize
+        } else {
+            input.u8()? as usize
+        };
+        let mut value = Reader(input.take(len)?);
+        match code {
+            ATTR_AS_PATH => {
+                while !value.0.is_empty() {
+                    let segment_type = value.u8()?;
+                    for _ in 0..value.u8()? {
+                        let asn = value.u32()?;
+                        if segment_type == AS_SEQUENCE {
+                            update.as_path.push(asn);
+                        }
+                    }
+                }
+            }
+            ATTR_COMMUNITY => {
+                while !value.0.is_empty() {
+                    update.communities.push(value.u32()?);
+                }
+            }
+            ATTR_MP_REACH_NLRI => {
+                let (afi, safi) = (value.u16()?, value.u8()?);
+                let next_hop_len = value.u8()? as usize;
+                value.take(next_hop_len)?;
+                // Reserved.
+                value.u8()?;
+                if safi == SAFI_UNICAST {
+                    read_nlri(value, afi, &mut update.announced)?;
+                }
+            }
+            ATTR_MP_UNREACH_NLRI => {
+                let (afi, safi) = (value.u16()?, value.u8()?);
+                if safi == SAFI_UNICAST {
+                    read_nlri(value, afi, &mut update.withdrawn)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attr(code: u8, value: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x40, code, value.len() as u8];
+        out.extend_from_slice(value);
+        out
+    }
+
+    fn record(afi: u16, peer: &[u8], withdrawn: &[u8], attrs: &[u8], nlri: &[u8]) -> Vec<u8> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&(withdrawn.len() as u16).to_be_bytes());
+        message.extend_from_slice(withdrawn);
+        message.extend_from_slice(&(attrs.len() as u16).to_be_bytes());
+        message.extend_from_slice(attrs);
+        message.extend_from_slice(nlri);
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&64496u32.to_be_bytes());
+        body.extend_from_slice(&[0; 6]);
+        body.extend_from_slice(&afi.to_be_bytes());
+        body.extend_from_slice(peer);
+        body.extend_from_slice(&vec![0; peer.len()]);
+        body.extend_from_slice(&[0xff; 16]);
+        body.extend_from_slice(&(19 + message.len() as u16).to_be_bytes());
+        body.push(BGP_UPDATE);
+        body.extend_from_slice(&message);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&1717200300u32.to_be_bytes());
+        out.extend_from_slice(&MRT_BGP4MP.to_be_bytes());
+        out.extend_from_slice(&BGP4MP_MESSAGE_AS4.to_be_bytes());
+        out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    fn decode(data: &[u8]) -> Result<Vec<Update>, String> {
+        let mut updates = Vec::new();
+        let mut input = Reader(data);
+        while !input.0.is_empty() {
+            read_record(&mut input, &mut |u: &Update| updates.push(u.clone()))?;
+        }
+        Ok(updates)
+    }
+
+    fn prefixes(prefixes: &[Prefix]) -> Vec<String> {
+        prefixes.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn ipv4_session() {
+        let mut as_path = vec![AS_SEQUENCE, 2];
+        as_path.extend_from_slice(&64496u32.to_be_bytes());
+        as_path.extend_from_slice(&64511u32.to_be_bytes());
+        let mut attrs = attr(ATTR_AS_PATH, &as_path);
+        attrs.extend(attr(ATTR_COMMUNITY, &[0xff, 0xff, 0x02, 0x9a]));
+        let data = record(
+            AFI_IPV4,
+            &[192, 0, 2, 1],
+            &[24, 198, 51, 100],
+            &attrs,
+            &[24, 192, 0, 2, 25, 203, 0, 113, 128],
+        );
+        let updates = decode(&data).unwrap();
+        assert_eq!(updates.len(), 1);
+        let u = &updates[0];
+        assert_eq!(u.time, 1717200300);
+        assert_eq!(
+            (u.peer_ip, u.peer_asn),
+            ("192.0.2.1".parse().unwrap(), 64496)
+        );
+        assert_eq!(prefixes(&u.withdrawn), ["198.51.100.0/24"]);
+        assert_eq!(prefixes(&u.announced), ["192.0.2.0/24", "203.0.113.128/25"]);
+        assert_eq!(u.as_path, [64496, 64511]);
+        assert_eq!(u.communities, [65535 << 16 | 666]);
+    }
+
+    #[test]
+    fn ipv6_routes_in_mp_attributes() {
+        let mut mp_reach = vec![0, 2, SAFI_UNICAST, 16];
+        mp_reach.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8].repeat(4));
+        mp_reach.extend_from_slice(&[0, 32, 0x20, 0x01, 0x0d, 0xb8]);
+        let mut attrs = attr(ATTR_MP_REACH_NLRI, &mp_reach);
+        attrs.extend(attr(
+            ATTR_MP_UNREACH_NLRI,
+            &[0, 2, SAFI_UNICAST, 48, 0x20, 0x01, 0x0d, 0xb8, 0, 1],
+        ));
+        let data = record(AFI_IPV6, &[0x20; 16], &[], &attrs, &[]);
+        let u = &decode(&data).unwrap()[0];
+        assert!(u.peer_ip.is_ipv6());
+        assert_eq!(prefixes(&u.announced), ["2001:db8::/32"]);
+        assert_eq!(prefixes(&u.withdrawn), ["2001:db8:1::/48"]);
+    }
+
+    #[test]
+    fn malformed_records_are_errors() {
+        let data = record(AFI_IPV4, &[192, 0, 2, 1], &[], &[], &[33, 192, 0, 2, 0, 0]);
+        assert_eq!(decode(&data).unwrap_err(), "prefix length 33 out of range");
+        let data = record(AFI_IPV4, &[192, 0, 2, 1], &[], &[], &[24, 192, 0, 2]);
+        assert_eq!(decode(&data[..data.len() - 1]).unwrap_err(), "truncated");
+    }
+}
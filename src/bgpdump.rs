@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use crate::prefix::Prefix;
+use crate::resolve;
+use crate::rib::{Peer, Route};
+
+/// True if `path` holds `bgpdump -m` text rather than binary MRT.
+pub fn is_bgpdump_text(path: &Path) -> bool {
+    let mut head = [0u8; 11];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut head))
+        .is_ok()
+        && &head == b"TABLE_DUMP2"
+}
+
+/// Calls `f` for every RIB line of `bgpdump -m` output, i.e.
+/// `TABLE_DUMP2|time|B|peer_ip|peer_as|prefix|as_path|origin|next_hop|...|communities|...`,
+/// and returns the timestamp of the first line. AS_SET members (`{...}`) are
+/// skipped, as for MRT input.
+pub fn read_file<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    let error = |e: io::Error| format!("{}: {}", path.display(), e);
+    let reader = BufReader::new(File::open(path).map_err(error)?);
+    let mut dump_time = None;
+    let mut peers: HashMap<(IpAddr, u32), Peer> = HashMap::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line.map_err(error)?;
+        let fields: Vec<&str> = line.split('|').collect();
+        if fields.len() < 7 || fields[0] != "TABLE_DUMP2" || fields[2] != "B" {
+            continue;
+        }
+        let parsed = (
+            fields[1].parse::<u32>(),
+            fields[3].parse::<IpAddr>(),
+            fields[4].parse::<u32>(),
+            fields[5].parse::<Prefix>(),
+        );
+        let (time, peer_ip, peer_as, prefix) = match parsed {
+            (Ok(time), Ok(peer_ip), Ok(peer_as), Ok(prefix)) => (time, peer_ip, peer_as, prefix),
+            _ => {
+                eprintln!("warning: {}:{}: malformed line", path.display(), n + 1);
+                continue;
+            }
+        };
+        dump_time.get_or_insert(time);
+        let index = peers.len() as u16;
+        let peer = *peers.entry((peer_ip, peer_as)).or_insert(Peer {
+            index,
+            asn: peer_as,
+            ip: peer_ip,
+            bgp_id: Ipv4Addr::UNSPECIFIED,
+        });
+        let route = Route {
+            prefix,
+            peer: Some(peer),
+            as_path: fields[6]
+                .split(' ')
+                .filter_map(|asn| asn.parse().ok())
+                .collect(),
+            communities: fields
+                .get(11)
+                .map(|c| {
+                    c.split(' ')
+                        .filter_map(|c| resolve::parse_community(c).ok())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
+        f(&route);
+    }
+    Ok(dump_time.unwrap_or(0))
+}
@@ -0,0 +1,448 @@
+//! Generates synthetic TABLE_DUMP_V2 RIB files, or BGP4MP updates files, from
+//! a TOML description.
+//!
+//! ```toml
+//! timestamp = 1717200000
+//!
+//! [[peer]]
+//! asn = 64496
+//! ip = "192.0.2.1"
+//!
+//! [[route]]
+//! prefix = "203.0.113.0/24"
+//! path = [64496, 64511]
+//! peer = 0
+//! communities = ["65535:666"]
+//!
+//! [[update]]
+//! time = 1717200300
+//! peer = 0
+//! withdraw = ["203.0.113.0/24"]
+//! announce = ["198.51.100.0/24", "2001:db8::/32"]
+//! path = [64496, 64500]
+//! ```
+//!
+//! Updates are written as BGP4MP_MESSAGE_AS4 records. IPv4 prefixes go in the
+//! withdrawn routes and NLRI fields, IPv6 prefixes in MP_UNREACH_NLRI and
+//! MP_REACH_NLRI. Without routes, no peer index table is written.
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use structopt::StructOpt;
+
+const MRT_TABLE_DUMP_V2: u16 = 13;
+const MRT_BGP4MP: u16 = 16;
+const BGP4MP_MESSAGE_AS4: u16 = 4;
+const BGP_UPDATE: u8 = 2;
+const AFI_IPV4: u16 = 1;
+const AFI_IPV6: u16 = 2;
+const SAFI_UNICAST: u8 = 1;
+const PEER_INDEX_TABLE: u16 = 1;
+const RIB_IPV4_UNICAST: u16 = 2;
+const RIB_IPV6_UNICAST: u16 = 4;
+
+const ATTR_FLAG_TRANSITIVE: u8 = 0x40;
+const ATTR_FLAG_OPTIONAL: u8 = 0x80;
+const ATTR_FLAG_EXTENDED_LENGTH: u8 = 0x10;
+const ATTR_ORIGIN: u8 = 1;
+const ATTR_AS_PATH: u8 = 2;
+const ATTR_NEXT_HOP: u8 = 3;
+const ATTR_COMMUNITY: u8 = 8;
+const ATTR_MP_REACH_NLRI: u8 = 14;
+const ATTR_MP_UNREACH_NLRI: u8 = 15;
+const AS_SEQUENCE: u8 = 2;
+
+#[derive(StructOpt, Debug)]
+#[structopt(name = "mkmrt")]
+struct Opts {
+    #[structopt(parse(from_os_str))]
+    description: PathBuf,
+
+    #[structopt(short, long, parse(from_os_str), default_value = "./rib")]
+    output: PathBuf,
+}
+
+#[derive(Deserialize, Debug)]
+struct Description {
+    #[serde(default)]
+    timestamp: u32,
+    #[serde(default = "default_collector")]
+    collector_bgp_id: Ipv4Addr,
+    #[serde(default)]
+    view_name: String,
+    #[serde(default, rename = "peer")]
+    peers: Vec<Peer>,
+    #[serde(default, rename = "route")]
+    routes: Vec<Route>,
+    #[serde(default, rename = "update")]
+    updates: Vec<Update>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Peer {
+    asn: u32,
+    ip: IpAddr,
+    bgp_id: Option<Ipv4Addr>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Route {
+    prefix: String,
+    #[serde(default)]
+    path: Vec<u32>,
+    origin: Option<u32>,
+    #[serde(default)]
+    peer: u16,
+    /// Standard communities in `asn:value` form.
+    #[serde(default)]
+    communities: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Update {
+    time: u32,
+    #[serde(default)]
+    peer: u16,
+    #[serde(default)]
+    withdraw: Vec<String>,
+    #[serde(default)]
+    announce: Vec<String>,
+    #[serde(default)]
+    path: Vec<u32>,
+    origin: Option<u32>,
+    #[serde(default)]
+    communities: Vec<String>,
+}
+
+fn default_collector() -> Ipv4Addr {
+    Ipv4Addr::UNSPECIFIED
+}
+
+fn main() {
+    let opts: Opts = Opts::from_args();
+    let content = fs::read_to_string(&opts.description).unwrap();
+    let desc: Description = toml::from_str(&content).expect("invalid description");
+    if desc.peers.is_empty() {
+        panic!("description must contain at least one [[peer]]");
+    }
+
+    let mut out = Vec::new();
+    if !desc.routes.is_empty() || desc.updates.is_empty() {
+        write_record(
+            &mut out,
+            desc.timestamp,
+            MRT_TABLE_DUMP_V2,
+            PEER_INDEX_TABLE,
+            &peer_index_table(&desc),
+        );
+    }
+
+    // One RIB record per prefix, carrying an entry for every peer announcing it.
+    let mut grouped: BTreeMap<(IpAddr, u8), Vec<&Route>> = BTreeMap::new();
+    for route in &desc.routes {
+        grouped
+            .entry(parse_prefix(&route.prefix))
+            .or_default()
+            .push(route);
+    }
+    for (seq, ((addr, len), routes)) in grouped.iter().enumerate() {
+        let subtype = if addr.is_ipv4() {
+            RIB_IPV4_UNICAST
+        } else {
+            RIB_IPV6_UNICAST
+        };
+        let body = rib_record(&desc, seq as u32, *addr, *len, routes);
+        write_record(&mut out, desc.timestamp, MRT_TABLE_DUMP_V2, subtype, &body);
+    }
+
+    let mut updates: Vec<&Update> = desc.updates.iter().collect();
+    updates.sort_by_key(|u| u.time);
+    for update in updates {
+        let body = update_record(&desc, update);
+        write_record(&mut out, update.time, MRT_BGP4MP, BGP4MP_MESSAGE_AS4, &body);
+    }
+
+    fs::File::create(&opts.output)
+        .unwrap()
+        .write_all(&out)
+        .unwrap();
+}
+
+fn parse_prefix(s: &str) -> (IpAddr, u8) {
+    let (addr, len) = s.split_once('/').expect("prefix must be in addr/len form");
+    let addr: IpAddr = addr.parse().expect("invalid prefix address");
+    let len: u8 = len.parse().expect("invalid prefix length");
+    let max_len = if addr.is_ipv4() { 32 } else { 128 };
+    if len > max_len {
+        panic!("{}: prefix length exceeds /{}", s, max_len);
+    }
+    (addr, len)
+}
+
+fn write_record(out: &mut Vec<u8>, timestamp: u32, mrt_type: u16, subtype: u16, body: &[u8]) {
+    out.extend_from_slice(&timestamp.to_be_bytes());
+    out.extend_from_slice(&mrt_type.to_be_bytes());
+    out.extend_from_slice(&subtype.to_be_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(body);
+}
+
+fn peer_index_table(desc: &Description) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&desc.collector_bgp_id.octets());
+    body.extend_from_slice(&(desc.view_name.len() as u16).to_be_bytes());
+    body.extend_from_slice(desc.view_name.as_bytes());
+    body.extend_from_slice(&(desc.peers.len() as u16).to_be_bytes());
+    for peer in &desc.peers {
+        // Always advertise 4-byte peer AS numbers.
+        let mut peer_type = 0b10;
+        if peer.ip.is_ipv6() {
+            peer_type |= 0b01;
+        }
+        body.push(peer_type);
+        let bgp_id = peer.bgp_id.unwrap_or(match peer.ip {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        });
+        body.extend_from_slice(&bgp_id.octets());
+        push_addr(&mut body, peer.ip);
+        body.extend_from_slice(&peer.asn.to_be_bytes());
+    }
+    body
+}
+
+fn rib_record(desc: &Description, seq: u32, addr: IpAddr, len: u8, routes: &[&Route]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&seq.to_be_bytes());
+    body.push(len);
+    let octets = match addr {
+        IpAddr::V4(ip) => ip.octets().to_vec(),
+        IpAddr::V6(ip) => ip.octets().to_vec(),
+    };
+    body.extend_from_slice(&octets[..(len as usize).div_ceil(8)]);
+    body.extend_from_slice(&(routes.len() as u16).to_be_bytes());
+    for route in routes {
+        let peer = desc
+            .peers
+            .get(route.peer as usize)
+            .expect("route refers to an unknown peer");
+        let path = full_path(
+            &route.path,
+            route.origin,
+            &format!("route {}", route.prefix),
+        );
+        let mut attrs = path_attributes(&path, &route.communities);
+        push_rib_next_hop(&mut attrs, peer, addr.is_ipv4());
+        body.extend_from_slice(&route.peer.to_be_bytes());
+        body.extend_from_slice(&desc.timestamp.to_be_bytes());
+        body.extend_from_slice(&(attrs.len() as u16).to_be_bytes());
+        body.extend_from_slice(&attrs);
+    }
+    body
+}
+
+fn update_record(desc: &Description, update: &Update) -> Vec<u8> {
+    let peer = desc
+        .peers
+        .get(update.peer as usize)
+        .expect("update refers to an unknown peer");
+    let withdraw: Vec<(IpAddr, u8)> = update.withdraw.iter().map(|p| parse_prefix(p)).collect();
+    let announce: Vec<(IpAddr, u8)> = update.announce.iter().map(|p| parse_prefix(p)).collect();
+    let is_v4 = |(addr, _): &&(IpAddr, u8)| addr.is_ipv4();
+    let (withdraw_v4, withdraw_v6): (Vec<_>, Vec<_>) = withdraw.iter().partition(is_v4);
+    let (announce_v4, announce_v6): (Vec<_>, Vec<_>) = announce.iter().partition(is_v4);
+
+    let mut attrs = Vec::new();
+    if !announce.is_empty() {
+        let what = format!("update at {}", update.time);
+        let path = full_path(&update.path, update.origin, &what);
+        attrs = path_attributes(&path, &update.communities);
+    }
+    if !announce_v4.is_empty() {
+        let next_hop = match peer.ip {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        };
+        push_attr(
+            &mut attrs,
+            ATTR_FLAG_TRANSITIVE,
+            ATTR_NEXT_HOP,
+            &next_hop.octets(),
+        );
+    }
+    if !announce_v6.is_empty() {
+        let next_hop = match peer.ip {
+            IpAddr::V6(ip) => ip,
+            IpAddr::V4(_) => Ipv6Addr::UNSPECIFIED,
+        };
+        let mut mp_reach = AFI_IPV6.to_be_bytes().to_vec();
+        mp_reach.push(SAFI_UNICAST);
+        mp_reach.push(16);
+        mp_reach.extend_from_slice(&next_hop.octets());
+        // Reserved.
+        mp_reach.push(0);
+        mp_reach.extend_from_slice(&nlri(&announce_v6));
+        push_attr(
+            &mut attrs,
+            ATTR_FLAG_OPTIONAL,
+            ATTR_MP_REACH_NLRI,
+            &mp_reach,
+        );
+    }
+    if !withdraw_v6.is_empty() {
+        let mut mp_unreach = AFI_IPV6.to_be_bytes().to_vec();
+        mp_unreach.push(SAFI_UNICAST);
+        mp_unreach.extend_from_slice(&nlri(&withdraw_v6));
+        push_attr(
+            &mut attrs,
+            ATTR_FLAG_OPTIONAL,
+            ATTR_MP_UNREACH_NLRI,
+            &mp_unreach,
+        );
+    }
+
+    let mut message = Vec::new();
+    let withdrawn = nlri(&withdraw_v4);
+    message.extend_from_slice(&(withdrawn.len() as u16).to_be_bytes());
+    message.extend_from_slice(&withdrawn);
+    message.extend_from_slice(&(attrs.len() as u16).to_be_bytes());
+    message.extend_from_slice(&attrs);
+    message.extend_from_slice(&nlri(&announce_v4));
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&peer.asn.to_be_bytes());
+    // Local AS and interface index.
+    body.extend_from_slice(&0u32.to_be_bytes());
+    body.extend_from_slice(&0u16.to_be_bytes());
+    let afi = if peer.ip.is_ipv4() {
+        AFI_IPV4
+    } else {
+        AFI_IPV6
+    };
+    body.extend_from_slice(&afi.to_be_bytes());
+    push_addr(&mut body, peer.ip);
+    // Local address, of the same family as the peer's.
+    match peer.ip {
+        IpAddr::V4(_) => body.extend_from_slice(&desc.collector_bgp_id.octets()),
+        IpAddr::V6(_) => body.extend_from_slice(&Ipv6Addr::UNSPECIFIED.octets()),
+    }
+    body.extend_from_slice(&[0xff; 16]);
+    body.extend_from_slice(&(19 + message.len() as u16).to_be_bytes());
+    body.push(BGP_UPDATE);
+    body.extend_from_slice(&message);
+    body
+}
+
+/// Prefixes in BGP NLRI encoding.
+fn nlri(prefixes: &[&(IpAddr, u8)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for &&(addr, len) in prefixes {
+        let octets = match addr {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        };
+        out.push(len);
+        out.extend_from_slice(&octets[..(len as usize).div_ceil(8)]);
+    }
+    out
+}
+
+/// `path` with `origin` appended unless it already ends in it.
+fn full_path(path: &[u32], origin: Option<u32>, what: &str) -> Vec<u32> {
+    let mut path = path.to_vec();
+    if let Some(origin) = origin {
+        if path.last() != Some(&origin) {
+            path.push(origin);
+        }
+    }
+    if path.is_empty() {
+        panic!("{} needs a path or an origin", what);
+    }
+    path
+}
+
+/// ORIGIN, AS_PATH and COMMUNITY attributes.
+fn path_attributes(path: &[u32], communities: &[String]) -> Vec<u8> {
+    let mut attrs = Vec::new();
+    push_attr(&mut attrs, ATTR_FLAG_TRANSITIVE, ATTR_ORIGIN, &[0]);
+
+    let mut as_path = Vec::new();
+    for segment in path.chunks(u8::MAX as usize) {
+        as_path.push(AS_SEQUENCE);
+        as_path.push(segment.len() as u8);
+        for asn in segment {
+            as_path.extend_from_slice(&asn.to_be_bytes());
+        }
+    }
+    push_attr(&mut attrs, ATTR_FLAG_TRANSITIVE, ATTR_AS_PATH, &as_path);
+
+    if !communities.is_empty() {
+        let mut values = Vec::new();
+        for community in communities {
+            let (asn, value) = community
+                .split_once(':')
+                .expect("community must be in asn:value form");
+            let asn: u16 = asn.parse().expect("invalid community");
+            let value: u16 = value.parse().expect("invalid community");
+            values.extend_from_slice(&asn.to_be_bytes());
+            values.extend_from_slice(&value.to_be_bytes());
+        }
+        push_attr(
+            &mut attrs,
+            ATTR_FLAG_OPTIONAL | ATTR_FLAG_TRANSITIVE,
+            ATTR_COMMUNITY,
+            &values,
+        );
+    }
+    attrs
+}
+
+/// The next hop of a RIB entry: NEXT_HOP for IPv4 prefixes, the abbreviated
+/// MP_REACH_NLRI of TABLE_DUMP_V2 for IPv6 ones.
+fn push_rib_next_hop(attrs: &mut Vec<u8>, peer: &Peer, ipv4: bool) {
+    if ipv4 {
+        let next_hop = match peer.ip {
+            IpAddr::V4(ip) => ip,
+            IpAddr::V6(_) => Ipv4Addr::UNSPECIFIED,
+        };
+        push_attr(
+            attrs,
+            ATTR_FLAG_TRANSITIVE,
+            ATTR_NEXT_HOP,
+            &next_hop.octets(),
+        );
+    } else {
+        // RFC 6396 4.3.4: only the next hop length and address are kept.
+        let next_hop = match peer.ip {
+            IpAddr::V6(ip) => ip,
+            IpAddr::V4(_) => Ipv6Addr::UNSPECIFIED,
+        };
+        let mut mp_reach = vec![16];
+        mp_reach.extend_from_slice(&next_hop.octets());
+        push_attr(attrs, ATTR_FLAG_OPTIONAL, ATTR_MP_REACH_NLRI, &mp_reach);
+    }
+}
+
+fn push_attr(attrs: &mut Vec<u8>, flags: u8, type_code: u8, value: &[u8]) {
+    if value.len() > u8::MAX as usize {
+        attrs.push(flags | ATTR_FLAG_EXTENDED_LENGTH);
+        attrs.push(type_code);
+        attrs.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    } else {
+        attrs.push(flags);
+        attrs.push(type_code);
+        attrs.push(value.len() as u8);
+    }
+    attrs.extend_from_slice(value);
+}
+
+fn push_addr(out: &mut Vec<u8>, addr: IpAddr) {
+    match addr {
+        IpAddr::V4(ip) => out.extend_from_slice(&ip.octets()),
+        IpAddr::V6(ip) => out.extend_from_slice(&ip.octets()),
+    }
+}
@@ -0,0 +1,204 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+use crate::prefix::Prefix;
+use crate::rib::Route;
+
+/// True if `path` is a Unix socket, taken to be a BIRD control socket.
+pub fn is_bird_socket(path: &Path) -> bool {
+    path.metadata().is_ok_and(|m| m.file_type().is_socket())
+}
+
+/// Calls `f` for every route of `show route all` on a BIRD control socket and
+/// returns 0, as live state has no dump time.
+///
+/// Replies are `NNNN-text` lines, continued by lines starting with a space,
+/// up to a `0000` line; codes 8000 and up are errors. A route starts at a line
+/// leading with its prefix, or with `unicast` (BIRD 2) or `via` (BIRD 1) for
+/// further routes to the same prefix, and its AS path and communities follow
+/// as `BGP.as_path:` and `BGP.community:` attribute lines.
+pub fn read_socket<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    let error = |e: io::Error| format!("{}: {}", path.display(), e);
+    let mut stream = UnixStream::connect(path).map_err(error)?;
+    stream.write_all(b"show route all\n").map_err(error)?;
+    let reader = BufReader::new(stream.try_clone().map_err(error)?);
+    read_reply(reader, f).map_err(|e| format!("{}: {}", path.display(), e))?;
+    stream.write_all(b"quit\n").ok();
+    Ok(0)
+}
+
+/// Parses the reply to `show route all`, greeting included.
+fn read_reply<R: BufRead, F: FnMut(&Route)>(reader: R, f: &mut F) -> Result<(), String> {
+    let mut route: Option<Route> = None;
+    let mut prefix: Option<Prefix> = None;
+    let mut greeted = false;
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let code = line
+            .get(..4)
+            .filter(|c| c.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|c| c.parse::<u32>().ok());
+        let text = match code {
+            Some(_) => line.get(5..).unwrap_or(""),
+            None => line.get(1..).unwrap_or(""),
+        };
+        match code {
+            // The greeting ends in its own 0001 line before our command is answered.
+            Some(1) if !greeted => {
+                greeted = true;
+                continue;
+            }
+            Some(0) => break,
+            Some(code) if code >= 8000 => return Err(text.to_string()),
+            _ => {}
+        }
+        let text = text.trim();
+        let first = text.split_whitespace().next().unwrap_or("");
+        // Route lines name their protocol in brackets; BIRD 2 also lists the
+        // next hops of a route on `via` lines of their own, which do not.
+        let starts_route = if let Ok(p) = first.parse::<Prefix>() {
+            prefix = Some(p);
+            true
+        } else {
+            matches!(
+                first,
+                "unicast" | "via" | "blackhole" | "unreachable" | "prohibited"
+            ) && text.contains(" [")
+        };
+        if starts_route {
+            if let Some(route) = route.take() {
+                f(&route);
+            }
+            route = prefix.map(|prefix| Route {
+                prefix,
+                peer: None,
+                as_path: Vec::new(),
+                communities: Vec::new(),
+            });
+        } else if let (Some(route), Some((name, value))) = (route.as_mut(), text.split_once(':')) {
+            match name {
+                "BGP.as_path" => {
+                    route.as_path = value
+                        .split_whitespace()
+                        .filter_map(|asn| asn.parse().ok())
+                        .collect();
+                }
+                "BGP.community" => {
+                    route.communities = value.split_whitespace().filter_map(community).collect();
+                }
+                _ => {}
+            }
+        }
+    }
+    if let Some(route) = route.take() {
+        f(&route);
+    }
+    Ok(())
+}
+
+/// `(65535,666)` as printed by BIRD.
+fn community(s: &str) -> Option<u32> {
+    let (asn, value) = s.strip_prefix('(')?.strip_suffix(')')?.split_once(',')?;
+    Some((asn.parse::<u16>().ok()? as u32) << 16 | value.parse::<u16>().ok()? as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn routes(transcript: &str) -> Vec<(String, Vec<u32>, Vec<u32>)> {
+        let mut routes = Vec::new();
+        read_reply(transcript.as_bytes(), &mut |route: &Route| {
+            routes.push((
+                route.prefix.to_string(),
+                route.as_path.clone(),
+                route.communities.clone(),
+            ))
+        })
+        .unwrap();
+        routes
+    }
+
+    const BIRD1: &str = "\
+0001 BIRD 1.6.8 ready.
+1007-192.0.2.0/24       via 198.51.100.1 on eth0 [peer1 2024-06-01] * (100) [AS64511i]
+1008-\tType: BGP unicast univ
+1012-\tBGP.origin: IGP
+ \tBGP.as_path: 64496 64511
+ \tBGP.next_hop: 198.51.100.1
+ \tBGP.local_pref: 100
+ \tBGP.community: (64496,100) (65535,666)
+1007-                   via 198.51.100.2 on eth0 [peer2 2024-06-01] (100) [AS64511i]
+1008-\tType: BGP unicast univ
+1012-\tBGP.origin: IGP
+ \tBGP.as_path: 64497 64511
+ \tBGP.next_hop: 198.51.100.2
+1007-203.0.113.0/24     unreachable [static1 2024-06-01] * (200)
+1008-\tType: static unicast univ
+0000 
+";
+
+    const BIRD2: &str = "\
+0001 BIRD 2.0.12 ready.
+1007-Table master4:
+ 192.0.2.0/24         unicast [peer1 2024-06-01] * (100) [AS64511i]
+1008-\tvia 198.51.100.1 on eth0
+1012-\tType: BGP univ
+ \tBGP.origin: IGP
+ \tBGP.as_path: 64496 64511
+ \tBGP.next_hop: 198.51.100.1
+ \tBGP.local_pref: 100
+ \tBGP.community: (64496,100) (65535,666)
+1007-                     unicast [peer2 2024-06-01] (100) [AS64511i]
+1008-\tvia 198.51.100.2 on eth0 weight 1
+ \tvia 198.51.100.3 on eth1 weight 1
+1012-\tType: BGP univ
+ \tBGP.origin: IGP
+ \tBGP.as_path: 64497 64511
+ \tBGP.next_hop: 198.51.100.2
+ 
+1007-Table master6:
+ 2001:db8::/32        unicast [peer3 2024-06-01] * (100) [AS64500i]
+1008-\tvia 2001:db8:ffff::1 on eth0
+1012-\tType: BGP univ
+ \tBGP.origin: IGP
+ \tBGP.as_path: 64498 64500
+0000 
+";
+
+    fn expected() -> Vec<(String, Vec<u32>, Vec<u32>)> {
+        vec![
+            (
+                "192.0.2.0/24".to_string(),
+                vec![64496, 64511],
+                vec![64496 << 16 | 100, 65535 << 16 | 666],
+            ),
+            ("192.0.2.0/24".to_string(), vec![64497, 64511], vec![]),
+        ]
+    }
+
+    #[test]
+    fn bird1_routes() {
+        let mut expected = expected();
+        expected.push(("203.0.113.0/24".to_string(), vec![], vec![]));
+        assert_eq!(routes(BIRD1), expected);
+    }
+
+    #[test]
+    fn bird2_next_hops_do_not_start_routes() {
+        let mut expected = expected();
+        expected.push(("2001:db8::/32".to_string(), vec![64498, 64500], vec![]));
+        assert_eq!(routes(BIRD2), expected);
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let reply = "0001 BIRD 2.0.12 ready.\n8001 Access denied\n";
+        assert_eq!(
+            read_reply(reply.as_bytes(), &mut |_: &Route| {}),
+            Err("Access denied".to_string())
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use crate::prefix::Prefix;
+use crate::rib::Route;
+
+/// True if `path` holds `show ip bgp` / `show bgp ipv6 unicast` text.
+pub fn is_cisco_table(path: &Path) -> bool {
+    let mut head = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(4096).read_to_end(&mut head))
+        .is_ok()
+        && String::from_utf8_lossy(&head).contains("BGP table version is")
+}
+
+/// Calls `f` for every valid (`*`) entry of a pasted Cisco-style BGP table.
+///
+/// Columns are located from the `Network ... Path` header line: status codes
+/// sit left of `Network`, and the AS path plus origin code start at `Path`.
+/// Entries without a network repeat the one above, networks too long for their
+/// column wrap the rest of the entry onto the next line, and IPv4 networks
+/// without a length take their classful one, all as IOS prints them. The text
+/// carries no dump time, so 0 is returned.
+pub fn read_file<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut columns: Option<(usize, usize)> = None;
+    let mut network: Option<Prefix> = None;
+    let mut valid = false;
+    for line in content.lines() {
+        if line.contains("Network") && line.contains("Path") {
+            columns = line.find("Network").zip(line.find("Path"));
+            continue;
+        }
+        let (network_col, path_col) = match columns {
+            Some(columns) => columns,
+            None => continue,
+        };
+        let status = line.get(..network_col).unwrap_or(line).trim();
+        let rest = line.get(network_col..).unwrap_or("");
+        if !status.is_empty() {
+            valid = status.contains('*');
+            if !rest.starts_with(' ') {
+                network = rest.split_whitespace().next().and_then(parse_network);
+            }
+        } else if rest.trim().is_empty() {
+            continue;
+        }
+        let path = match line.get(path_col..) {
+            Some(path) if valid => path,
+            _ => continue,
+        };
+        let prefix = match network {
+            Some(prefix) => prefix,
+            None => continue,
+        };
+        let route = Route {
+            prefix,
+            peer: None,
+            as_path: path
+                .split_whitespace()
+                .filter_map(|asn| asn.parse().ok())
+                .collect(),
+            communities: Vec::new(),
+        };
+        f(&route);
+    }
+    Ok(0)
+}
+
+fn parse_network(s: &str) -> Option<Prefix> {
+    if let Ok(prefix) = s.parse() {
+        return Some(prefix);
+    }
+    let addr: Ipv4Addr = s.parse().ok()?;
+    let len = match addr.octets()[0] {
+        0..=127 => 8,
+        128..=191 => 16,
+        _ => 24,
+    };
+    Some(Prefix::new(IpAddr::V4(addr), len))
+}
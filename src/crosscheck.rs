@@ -0,0 +1,116 @@
+use std::collections::{BTreeSet, HashMap};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+use structopt::StructOpt;
+
+use crate::http_cache::HttpCache;
+use crate::prefix::Prefix;
+use crate::rib;
+
+const RIPESTAT_ANNOUNCED_PREFIXES: &str =
+    "https://stat.ripe.net/data/announced-prefixes/data.json?resource=AS";
+
+#[derive(StructOpt, Debug)]
+pub struct CrosscheckOpts {
+    /// Directory for cached RIPEstat responses [default: ~/.cache/bgptools/http]
+    #[structopt(long, parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+
+    /// Seconds a cached response stays fresh
+    #[structopt(long, default_value = "86400")]
+    cache_ttl: u64,
+
+    /// Minimum milliseconds between two requests to RIPEstat
+    #[structopt(long, default_value = "1000")]
+    min_interval: u64,
+
+    /// Answer from the cache only, never touching the network
+    #[structopt(long)]
+    offline: bool,
+
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Response {
+    data: Data,
+}
+
+#[derive(Deserialize, Debug)]
+struct Data {
+    prefixes: Vec<AnnouncedPrefix>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AnnouncedPrefix {
+    prefix: String,
+}
+
+/// Compares the prefixes each ASN originates in the local RIB with what RIPEstat
+/// reports, printing every prefix only one side knows about.
+pub fn run(mrt_file: &Path, opts: CrosscheckOpts) {
+    let mut local: HashMap<u32, BTreeSet<Prefix>> = opts
+        .asns
+        .iter()
+        .map(|&asn| (asn, BTreeSet::new()))
+        .collect();
+    rib::for_each_route(mrt_file, |route| {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some(prefixes) = local.get_mut(&origin) {
+            prefixes.insert(route.prefix);
+        }
+    });
+
+    let mut cache = HttpCache::new(
+        opts.cache_dir.unwrap_or_else(HttpCache::default_dir),
+        Duration::from_secs(opts.cache_ttl),
+        Duration::from_millis(opts.min_interval),
+        opts.offline,
+    );
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for asn in &opts.asns {
+        let local = &local[asn];
+        let remote = match fetch_announced(&mut cache, *asn) {
+            Ok(remote) => remote,
+            Err(e) => {
+                eprintln!("warning: AS{}: RIPEstat query failed: {}", asn, e);
+                continue;
+            }
+        };
+        for prefix in local.difference(&remote) {
+            writeln!(out, "AS{}\tlocal-only\t{}", asn, prefix).unwrap();
+        }
+        for prefix in remote.difference(local) {
+            writeln!(out, "AS{}\tripestat-only\t{}", asn, prefix).unwrap();
+        }
+        writeln!(
+            out,
+            "# AS{}: {} shared, {} local-only, {} ripestat-only",
+            asn,
+            local.intersection(&remote).count(),
+            local.difference(&remote).count(),
+            remote.difference(local).count()
+        )
+        .unwrap();
+    }
+    out.flush().unwrap();
+}
+
+fn fetch_announced(cache: &mut HttpCache, asn: u32) -> Result<BTreeSet<Prefix>, String> {
+    let url = format!("{}{}", RIPESTAT_ANNOUNCED_PREFIXES, asn);
+    let body = cache.get(&url)?;
+    let response: Response = serde_json::from_str(&body).map_err(|e| e.to_string())?;
+    response
+        .data
+        .prefixes
+        .iter()
+        .map(|p| p.prefix.parse())
+        .collect()
+}
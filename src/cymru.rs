@@ -0,0 +1,358 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use crate::output::Entry;
+
+/// External IP-to-ASN services the output can be checked against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VerifyService {
+    /// Team Cymru's `origin.asn.cymru.com` DNS zone.
+    Cymru,
+}
+
+impl FromStr for VerifyService {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cymru" => Ok(VerifyService::Cymru),
+            _ => Err(format!("unknown verification service: {}", s)),
+        }
+    }
+}
+
+const TYPE_TXT: u16 = 16;
+const CLASS_IN: u16 = 1;
+const FLAG_TC: u16 = 0x0200;
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Looks up the first address of up to `sample` evenly spread entries in Team
+/// Cymru's origin zone and warns where none of the origins it reports matches
+/// the local attribution. Only that one address is checked, so a more specific
+/// announced by another network elsewhere inside the prefix goes unnoticed.
+/// Lookup failures are warned about and skipped.
+pub fn check(entries: &[Entry], sample: usize) {
+    let resolver = resolver();
+    let step = (entries.len() / sample.max(1)).max(1);
+    let mut disagreements = 0;
+    let mut checked = 0;
+    for entry in entries.iter().step_by(step).take(sample) {
+        if entry.origins.is_empty() {
+            continue;
+        }
+        let name = origin_name(entry.prefix.addr);
+        let remote: Vec<u32> = match query_txt(resolver, &name) {
+            // "13335 | 1.0.0.0/24 | AU | apnic | 2011-08-11", several origins space-separated.
+            Ok(records) => records
+                .iter()
+                .filter_map(|r| r.split('|').next())
+                .flat_map(|asns| asns.split_whitespace())
+                .filter_map(|asn| asn.parse().ok())
+                .collect(),
+            Err(e) => {
+                eprintln!("warning: {}: Team Cymru lookup failed: {}", entry.prefix, e);
+                continue;
+            }
+        };
+        checked += 1;
+        if !remote.iter().any(|asn| entry.origins.contains(asn)) {
+            disagreements += 1;
+            let fmt = |asns: &[u32]| -> String {
+                let asns: Vec<String> = asns.iter().map(|a| format!("AS{}", a)).collect();
+                if asns.is_empty() {
+                    "none".to_string()
+                } else {
+                    asns.join(" ")
+                }
+            };
+            eprintln!(
+                "warning: {}: Team Cymru reports origin {}, local table {}",
+                entry.prefix,
+                fmt(&remote),
+                fmt(&entry.origins)
+            );
+        }
+    }
+    eprintln!(
+        "verified {} prefixes against Team Cymru, {} disagreements",
+        checked, disagreements
+    );
+}
+
+/// `4.3.2.1.origin.asn.cymru.com`, or reversed nibbles under `origin6` for IPv6.
+fn origin_name(addr: IpAddr) -> String {
+    match addr {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.origin.asn.cymru.com", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: Vec<String> = format!("{:032x}", u128::from(v6))
+                .chars()
+                .rev()
+                .map(String::from)
+                .collect();
+            format!("{}.origin6.asn.cymru.com", nibbles.join("."))
+        }
+    }
+}
+
+/// First nameserver of /etc/resolv.conf, or a local resolver.
+fn resolver() -> SocketAddr {
+    let nameserver = fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines()
+                .filter_map(|line| line.strip_prefix("nameserver"))
+                .find_map(|addr| addr.trim().parse::<IpAddr>().ok())
+        })
+        .unwrap_or(IpAddr::from([127, 0, 0, 1]));
+    SocketAddr::new(nameserver, 53)
+}
+
+/// A recursive TXT query for `name` with a random ID, so that an off-path
+/// attacker cannot guess it to spoof an answer.
+fn build_query(name: &str) -> Result<Vec<u8>, String> {
+    let mut id = [0u8; 2];
+    getrandom::getrandom(&mut id).map_err(|e| e.to_string())?;
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id);
+    // Recursion desired, one question.
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&TYPE_TXT.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+/// Asks over UDP and, when the answer does not fit in a datagram, again over
+/// TCP. Datagrams from other addresses or not answering the query are ignored.
+fn query_txt(resolver: SocketAddr, name: &str) -> Result<Vec<String>, String> {
+    let query = build_query(name)?;
+    let bind: SocketAddr = if resolver.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    socket
+        .send_to(&query, resolver)
+        .map_err(|e| e.to_string())?;
+    let deadline = Instant::now() + TIMEOUT;
+    let mut buf = [0u8; 1500];
+    let len = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err("timed out waiting for the DNS response".to_string());
+        }
+        socket
+            .set_read_timeout(Some(remaining))
+            .map_err(|e| e.to_string())?;
+        let (len, from) = socket.recv_from(&mut buf).map_err(|e| e.to_string())?;
+        if from == resolver && answers(&buf[..len], &query) {
+            break len;
+        }
+    };
+    let flags = u16::from_be_bytes([buf[2], buf[3]]);
+    if flags & FLAG_TC == 0 {
+        return parse_txt_response(&buf[..len]);
+    }
+    let response = query_tcp(resolver, &query)?;
+    if !answers(&response, &query) {
+        return Err("DNS response over TCP does not match the query".to_string());
+    }
+    parse_txt_response(&response)
+}
+
+/// Sends `query` over TCP with the two-byte length prefix of RFC 1035 4.2.2.
+fn query_tcp(resolver: SocketAddr, query: &[u8]) -> Result<Vec<u8>, String> {
+    let mut stream = TcpStream::connect_timeout(&resolver, TIMEOUT).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .map_err(|e| e.to_string())?;
+    let mut message = (query.len() as u16).to_be_bytes().to_vec();
+    message.extend_from_slice(query);
+    stream.write_all(&message).map_err(|e| e.to_string())?;
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).map_err(|e| e.to_string())?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream
+        .read_exact(&mut response)
+        .map_err(|e| e.to_string())?;
+    Ok(response)
+}
+
+/// Whether `msg` is a response carrying the ID and the question of `query`.
+/// Names compare case-insensitively, as resolvers may echo them with 0x20
+/// case randomization applied.
+fn answers(msg: &[u8], query: &[u8]) -> bool {
+    let question = &query[12..];
+    msg.len() >= query.len()
+        && msg[..2] == query[..2]
+        && msg[2] & 0x80 != 0
+        && msg[4..6] == [0, 1]
+        && msg[12..query.len()].eq_ignore_ascii_case(question)
+}
+
+fn parse_txt_response(msg: &[u8]) -> Result<Vec<String>, String> {
+    let truncated = || "truncated DNS response".to_string();
+    let u16_at = |i: usize| -> Result<u16, String> {
+        msg.get(i..i + 2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .ok_or_else(truncated)
+    };
+    match u16_at(2)? & 0x000f {
+        0 => {}
+        3 => return Ok(Vec::new()),
+        rcode => return Err(format!("DNS error code {}", rcode)),
+    }
+    let (questions, answers) = (u16_at(4)?, u16_at(6)?);
+    let mut i = 12;
+    for _ in 0..questions {
+        i = skip_name(msg, i).ok_or_else(truncated)? + 4;
+    }
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        i = skip_name(msg, i).ok_or_else(truncated)?;
+        let rtype = u16_at(i)?;
+        let rdlength = u16_at(i + 8)? as usize;
+        let rdata = msg.get(i + 10..i + 10 + rdlength).ok_or_else(truncated)?;
+        i += 10 + rdlength;
+        if rtype != TYPE_TXT {
+            continue;
+        }
+        let mut text = String::new();
+        let mut j = 0;
+        while j < rdata.len() {
+            let n = rdata[j] as usize;
+            let chunk = rdata.get(j + 1..j + 1 + n).ok_or_else(truncated)?;
+            text.push_str(&String::from_utf8_lossy(chunk));
+            j += 1 + n;
+        }
+        records.push(text);
+    }
+    Ok(records)
+}
+
+/// Index just past the (possibly compressed) domain name starting at `i`.
+fn skip_name(msg: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        let len = *msg.get(i)?;
+        match len {
+            0 => return Some(i + 1),
+            l if l & 0xc0 == 0xc0 => return Some(i + 2),
+            l => i += 1 + l as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::thread;
+
+    const NAME: &str = "1.2.0.192.origin.asn.cymru.com";
+
+    /// A response to `query` with `flags` and a TXT answer per string.
+    fn response(query: &[u8], flags: u16, txt: &[&str]) -> Vec<u8> {
+        let mut msg = query[..2].to_vec();
+        msg.extend_from_slice(&(0x8180 | flags).to_be_bytes());
+        msg.extend_from_slice(&[0, 1, 0, txt.len() as u8, 0, 0, 0, 0]);
+        msg.extend_from_slice(&query[12..]);
+        for text in txt {
+            msg.extend_from_slice(&[0xc0, 12]);
+            msg.extend_from_slice(&TYPE_TXT.to_be_bytes());
+            msg.extend_from_slice(&CLASS_IN.to_be_bytes());
+            msg.extend_from_slice(&[0, 0, 0, 60]);
+            msg.extend_from_slice(&(text.len() as u16 + 1).to_be_bytes());
+            msg.push(text.len() as u8);
+            msg.extend_from_slice(text.as_bytes());
+        }
+        msg
+    }
+
+    #[test]
+    fn responses_must_match_the_query() {
+        let query = build_query(NAME).unwrap();
+        let ok = response(
+            &query,
+            0,
+            &["64496 | 192.0.2.0/24 | ZZ | test | 2024-01-01"],
+        );
+        assert!(answers(&ok, &query));
+        assert_eq!(
+            parse_txt_response(&ok).unwrap(),
+            ["64496 | 192.0.2.0/24 | ZZ | test | 2024-01-01"]
+        );
+
+        let mut other_id = ok.clone();
+        other_id[0] ^= 0xff;
+        assert!(!answers(&other_id, &query));
+        let other_name = response(
+            &build_query("1.2.0.198.origin.asn.cymru.com").unwrap(),
+            0,
+            &[],
+        );
+        let mut other_name_same_id = other_name.clone();
+        other_name_same_id[..2].copy_from_slice(&query[..2]);
+        assert!(!answers(&other_name_same_id, &query));
+        let mut not_a_response = ok.clone();
+        not_a_response[2] &= 0x7f;
+        assert!(!answers(&not_a_response, &query));
+        assert!(!answers(&ok[..query.len() - 1], &query));
+
+        let mut mixed_case = ok.clone();
+        mixed_case[23] = b'O';
+        mixed_case[25] = b'I';
+        assert!(answers(&mixed_case, &query));
+    }
+
+    #[test]
+    fn query_ids_are_random() {
+        let ids: Vec<[u8; 2]> = (0..8)
+            .map(|_| build_query(NAME).unwrap()[..2].try_into().unwrap())
+            .collect();
+        assert!(ids.iter().any(|id| *id != ids[0]));
+    }
+
+    /// The resolver first answers with a wrong ID, which must be ignored, then
+    /// with a truncated answer, which must be retried over TCP.
+    #[test]
+    fn truncated_answers_are_retried_over_tcp() {
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let resolver = tcp.local_addr().unwrap();
+        let udp = UdpSocket::bind(resolver).unwrap();
+        let server = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            let (len, client) = udp.recv_from(&mut buf).unwrap();
+            let query = buf[..len].to_vec();
+            let mut spoofed = response(&query, 0, &["64511 | 192.0.2.0/24"]);
+            spoofed[1] ^= 0xff;
+            udp.send_to(&spoofed, client).unwrap();
+            udp.send_to(&response(&query, FLAG_TC, &[]), client)
+                .unwrap();
+
+            let (mut stream, _) = tcp.accept().unwrap();
+            let mut len = [0u8; 2];
+            stream.read_exact(&mut len).unwrap();
+            let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+            stream.read_exact(&mut query).unwrap();
+            let answer = response(&query, 0, &["64496 | 192.0.2.0/24", "64497 | 192.0.2.0/24"]);
+            stream
+                .write_all(&(answer.len() as u16).to_be_bytes())
+                .unwrap();
+            stream.write_all(&answer).unwrap();
+        });
+        let records = query_txt(resolver, NAME).unwrap();
+        server.join().unwrap();
+        assert_eq!(records, ["64496 | 192.0.2.0/24", "64497 | 192.0.2.0/24"]);
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use crate::output::Entry;
+use crate::prefix::Prefix;
+use crate::setop;
+
+/// One allocated or assigned block from an RIR delegated-extended file.
+#[derive(Debug)]
+pub struct Allocation {
+    first: u128,
+    last: u128,
+    pub rir: String,
+    /// Allocation date as `YYYYMMDD`, when the registry recorded one.
+    pub date: Option<u32>,
+    /// Opaque ID of the holding organization, shared by all its resources.
+    org: Option<String>,
+}
+
+impl Allocation {
+    pub fn date_string(&self) -> Option<String> {
+        self.date
+            .map(|d| format!("{:04}-{:02}-{:02}", d / 10000, d / 100 % 100, d % 100))
+    }
+}
+
+/// Allocations of all loaded delegated-extended files, sorted by first address.
+#[derive(Debug, Default)]
+pub struct Delegated {
+    v4: Vec<Allocation>,
+    v6: Vec<Allocation>,
+    /// ASN blocks as (first, last, opaque ID).
+    asns: Vec<(u32, u32, String)>,
+}
+
+impl Delegated {
+    pub fn load(paths: &[PathBuf]) -> Delegated {
+        let mut delegated = Delegated::default();
+        for path in paths {
+            let content = fs::read_to_string(path).unwrap();
+            for line in content.lines() {
+                delegated.add_line(line);
+            }
+        }
+        delegated.v4.sort_unstable_by_key(|a| a.first);
+        delegated.v6.sort_unstable_by_key(|a| a.first);
+        delegated
+    }
+
+    /// Parses `registry|cc|type|start|value|date|status[|opaque-id]`; version,
+    /// summary and comment lines as well as unallocated space are skipped.
+    fn add_line(&mut self, line: &str) {
+        let fields: Vec<&str> = line.split('|').collect();
+        if line.starts_with('#') || fields.len() < 7 {
+            return;
+        }
+        if !matches!(fields[6], "allocated" | "assigned") {
+            return;
+        }
+        let org = fields
+            .get(7)
+            .filter(|id| !id.is_empty())
+            .map(|id| id.to_string());
+        if fields[2] == "asn" {
+            if let (Ok(first), Ok(count), Some(org)) =
+                (fields[3].parse::<u32>(), fields[4].parse::<u32>(), org)
+            {
+                if count > 0 {
+                    self.asns.push((first, first + (count - 1), org));
+                }
+            }
+            return;
+        }
+        let start: IpAddr = match fields[3].parse() {
+            Ok(start) => start,
+            Err(_) => return,
+        };
+        let value: u128 = match fields[4].parse() {
+            Ok(value) => value,
+            Err(_) => return,
+        };
+        let (first, last, allocations) = match (fields[2], start) {
+            ("ipv4", IpAddr::V4(start)) if value > 0 => {
+                let first = u32::from(start) as u128;
+                (first, first + value - 1, &mut self.v4)
+            }
+            ("ipv6", IpAddr::V6(_)) if value <= 128 => {
+                let (first, last) = Prefix::new(start, value as u8).bounds();
+                (first, last, &mut self.v6)
+            }
+            _ => return,
+        };
+        allocations.push(Allocation {
+            first,
+            last,
+            rir: fields[0].to_string(),
+            date: fields[5].parse().ok().filter(|&d| d != 0),
+            org,
+        });
+    }
+
+    /// Opaque ID of the organization holding `asn`.
+    fn org_of(&self, asn: u32) -> Option<&str> {
+        self.asns
+            .iter()
+            .find(|(first, last, _)| (*first..=*last).contains(&asn))
+            .map(|(_, _, org)| org.as_str())
+    }
+
+    /// The allocation containing the first address of `prefix`.
+    pub fn lookup(&self, prefix: &Prefix) -> Option<&Allocation> {
+        let allocations = if prefix.is_ipv4() { &self.v4 } else { &self.v6 };
+        let first = prefix.bounds().0;
+        let i = allocations.partition_point(|a| a.first <= first);
+        allocations[..i].last().filter(|a| a.last >= first)
+    }
+}
+
+/// Adds the address space allocated to the organizations holding `asns` that
+/// no entry covers yet, attributed to the organization's requested ASN(s).
+/// Needs delegated-extended files with opaque IDs.
+pub fn include_allocated(entries: &mut Vec<Entry>, delegated: &Delegated, asns: &HashSet<u32>) {
+    let mut sorted: Vec<u32> = asns.iter().copied().collect();
+    sorted.sort_unstable();
+    let mut orgs: Vec<(&str, Vec<u32>)> = Vec::new();
+    for asn in sorted {
+        match delegated.org_of(asn) {
+            Some(org) => match orgs.iter_mut().find(|(o, _)| *o == org) {
+                Some((_, asns)) => asns.push(asn),
+                None => orgs.push((org, vec![asn])),
+            },
+            None => eprintln!("warning: AS{} not found in the delegated files", asn),
+        }
+    }
+    for (ipv4, allocations) in [(true, &delegated.v4), (false, &delegated.v6)] {
+        let announced = setop::merge(
+            entries
+                .iter()
+                .filter(|e| e.prefix.is_ipv4() == ipv4)
+                .map(|e| e.prefix.bounds())
+                .collect(),
+        );
+        for (org, origins) in &orgs {
+            let allocated = setop::merge(
+                allocations
+                    .iter()
+                    .filter(|a| a.org.as_deref() == Some(*org))
+                    .map(|a| (a.first, a.last))
+                    .collect(),
+            );
+            for (first, last) in setop::subtract(&allocated, &announced) {
+                for prefix in setop::to_prefixes(ipv4, first, last) {
+                    entries.push(Entry {
+                        prefix,
+                        origins: origins.clone(),
+                        overridden: false,
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Parses a `YYYY-MM-DD` date into the `YYYYMMDD` form used by delegated files.
+pub fn parse_date(s: &str) -> Result<u32, String> {
+    let parts: Vec<&str> = s.split('-').collect();
+    if parts.len() != 3 {
+        return Err(format!("{}: date must be in YYYY-MM-DD form", s));
+    }
+    let mut date = 0;
+    for (part, width) in parts.iter().zip([10000, 100, 1]) {
+        let n: u32 = part.parse().map_err(|e| format!("{}: {}", s, e))?;
+        date += n * width;
+    }
+    Ok(date)
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::rib;
+use crate::size::{self, Ranges};
+
+#[derive(StructOpt, Debug)]
+pub struct DualstackOpts {
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+/// Classifies each ASN as v4-only, v6-only, dual-stack or unseen by what it
+/// originates, with its space in /24 and /48 equivalents and the share of
+/// those units that are IPv6.
+pub fn run(mrt_file: &Path, opts: DualstackOpts) {
+    let mut ranges: HashMap<u32, (Ranges, Ranges)> = opts
+        .asns
+        .iter()
+        .map(|&asn| (asn, Default::default()))
+        .collect();
+    rib::for_each_route(mrt_file, |route| {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some((v4, v6)) = ranges.get_mut(&origin) {
+            if route.prefix.is_ipv4() {
+                v4.push(route.prefix.bounds());
+            } else {
+                v6.push(route.prefix.bounds());
+            }
+        }
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for asn in &opts.asns {
+        let (v4, v6) = ranges.remove(asn).unwrap_or_default();
+        let slash24 = size::merged_size(v4) as f64 / (1u128 << 8) as f64;
+        let slash48 = size::merged_size(v6) as f64 / (1u128 << 80) as f64;
+        let class = match (slash24 > 0.0, slash48 > 0.0) {
+            (true, true) => "dual-stack",
+            (true, false) => "v4-only",
+            (false, true) => "v6-only",
+            (false, false) => "unseen",
+        };
+        let v6_share = if slash24 + slash48 > 0.0 {
+            slash48 / (slash24 + slash48)
+        } else {
+            0.0
+        };
+        writeln!(
+            out,
+            "AS{}\t{}\t{:.2} /24\t{:.2} /48\t{:.4}",
+            asn, class, slash24, slash48, v6_share
+        )
+        .unwrap();
+    }
+    out.flush().unwrap();
+}
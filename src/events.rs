@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::prefix::Prefix;
+use crate::rib::{self, Update};
+
+#[derive(StructOpt, Debug)]
+pub struct EventsOpts {
+    /// Only report prefixes equal to or more specific than this one
+    #[structopt(long, number_of_values = 1)]
+    prefix: Vec<Prefix>,
+
+    /// Only report routes originated by these ASNs, before or after the event
+    #[structopt(required_unless = "prefix", min_values = 1)]
+    asns: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Announce,
+    Withdraw,
+    OriginChange,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+struct Event {
+    time: u32,
+    event: Kind,
+    peer_asn: u32,
+    peer_ip: IpAddr,
+    prefix: String,
+    /// Origin after the event; for withdrawals, the origin of the withdrawn
+    /// route when it is known.
+    origin: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_origin: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    as_path: Vec<u32>,
+}
+
+/// Origin of every route by peer and prefix, as the updates leave it.
+struct Table<'a> {
+    origins: HashMap<((IpAddr, u32), Prefix), Option<u32>>,
+    opts: &'a EventsOpts,
+}
+
+impl Table<'_> {
+    fn selected(&self, prefix: Prefix, origins: &[Option<u32>]) -> bool {
+        let prefix_ok =
+            self.opts.prefix.is_empty() || (self.opts.prefix.iter()).any(|&p| covers(p, prefix));
+        let asn_ok = self.opts.asns.is_empty()
+            || (origins.iter().flatten()).any(|asn| self.opts.asns.contains(asn));
+        prefix_ok && asn_ok
+    }
+
+    /// The events of `update`. Announcements that only change the path of a
+    /// route are not reported.
+    fn apply(&mut self, update: &Update) -> Vec<Event> {
+        let peer = (update.peer_ip, update.peer_asn);
+        let event = |kind, prefix: Prefix, origin, previous_origin, as_path: &[u32]| Event {
+            time: update.time,
+            event: kind,
+            peer_asn: update.peer_asn,
+            peer_ip: update.peer_ip,
+            prefix: prefix.to_string(),
+            origin,
+            previous_origin,
+            as_path: as_path.to_vec(),
+        };
+        let mut events = Vec::new();
+        for &prefix in &update.withdrawn {
+            let origin = self.origins.remove(&(peer, prefix)).flatten();
+            if self.selected(prefix, &[origin]) {
+                events.push(event(Kind::Withdraw, prefix, origin, None, &[]));
+            }
+        }
+        let origin = update.as_path.last().copied();
+        for &prefix in &update.announced {
+            let (kind, previous) = match self.origins.insert((peer, prefix), origin) {
+                None => (Kind::Announce, None),
+                Some(previous) if previous != origin => (Kind::OriginChange, previous),
+                Some(_) => continue,
+            };
+            if self.selected(prefix, &[origin, previous]) {
+                events.push(event(kind, prefix, origin, previous, &update.as_path));
+            }
+        }
+        events
+    }
+}
+
+/// True if `inner` is `outer` or one of its more specifics.
+fn covers(outer: Prefix, inner: Prefix) -> bool {
+    // Addresses left-aligned in 128 bits, so that a mask of the top `len`
+    // bits works for both families.
+    let bits = |addr: IpAddr| match addr {
+        IpAddr::V4(a) => (u32::from(a) as u128) << 96,
+        IpAddr::V6(a) => u128::from(a),
+    };
+    let mask = u128::MAX.checked_shl(128 - outer.len as u32).unwrap_or(0);
+    outer.addr.is_ipv4() == inner.addr.is_ipv4()
+        && outer.len <= inner.len
+        && (bits(outer.addr) ^ bits(inner.addr)) & mask == 0
+}
+
+/// Writes the announcements, withdrawals and origin changes of the BGP4MP
+/// updates file as NDJSON, one event per line, for alerting pipelines.
+pub fn run(mrt_file: &Path, opts: EventsOpts) {
+    let mut table = Table {
+        origins: HashMap::new(),
+        opts: &opts,
+    };
+    let mut updates = Vec::new();
+    rib::for_each_update(mrt_file, |update| updates.push(update.clone()));
+    updates.sort_by_key(|u| u.time);
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for update in &updates {
+        for event in table.apply(update) {
+            serde_json::to_writer(&mut out, &event).unwrap();
+            writeln!(out).unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const PEER_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+
+    fn opts(args: &[&str]) -> EventsOpts {
+        EventsOpts::from_iter_safe(std::iter::once("events").chain(args.iter().copied())).unwrap()
+    }
+
+    fn update(time: u32, withdrawn: &[&str], announced: &[&str], as_path: &[u32]) -> Update {
+        Update {
+            time,
+            peer_asn: 64496,
+            peer_ip: PEER_IP,
+            withdrawn: withdrawn.iter().map(|s| s.parse().unwrap()).collect(),
+            announced: announced.iter().map(|s| s.parse().unwrap()).collect(),
+            as_path: as_path.to_vec(),
+            communities: Vec::new(),
+        }
+    }
+
+    fn kinds(
+        opts: &EventsOpts,
+        updates: &[Update],
+    ) -> Vec<(Kind, String, Option<u32>, Option<u32>)> {
+        let mut table = Table {
+            origins: HashMap::new(),
+            opts,
+        };
+        (updates.iter())
+            .flat_map(|u| table.apply(u))
+            .map(|e| (e.event, e.prefix, e.origin, e.previous_origin))
+            .collect()
+    }
+
+    #[test]
+    fn announce_change_and_withdraw() {
+        let updates = [
+            update(1, &[], &["192.0.2.0/24"], &[64496, 64511]),
+            update(2, &[], &["192.0.2.0/24"], &[64496, 64497, 64511]),
+            update(3, &[], &["192.0.2.0/24"], &[64496, 64500]),
+            update(4, &["192.0.2.0/24"], &[], &[]),
+        ];
+        assert_eq!(
+            kinds(&opts(&["64511"]), &updates),
+            [
+                (Kind::Announce, "192.0.2.0/24".into(), Some(64511), None),
+                (
+                    Kind::OriginChange,
+                    "192.0.2.0/24".into(),
+                    Some(64500),
+                    Some(64511)
+                ),
+            ]
+        );
+        assert_eq!(
+            kinds(&opts(&["64500"]), &updates),
+            [
+                (
+                    Kind::OriginChange,
+                    "192.0.2.0/24".into(),
+                    Some(64500),
+                    Some(64511)
+                ),
+                (Kind::Withdraw, "192.0.2.0/24".into(), Some(64500), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_filter_covers_more_specifics() {
+        let updates = [update(
+            1,
+            &["198.51.100.0/24"],
+            &[
+                "192.0.2.0/24",
+                "192.0.2.128/25",
+                "192.0.0.0/16",
+                "2001:db8::/32",
+            ],
+            &[64496, 64511],
+        )];
+        assert_eq!(
+            kinds(&opts(&["--prefix", "192.0.2.0/24"]), &updates),
+            [
+                (Kind::Announce, "192.0.2.0/24".into(), Some(64511), None),
+                (Kind::Announce, "192.0.2.128/25".into(), Some(64511), None),
+            ]
+        );
+        // Withdrawals of routes never seen carry no origin, so only a prefix
+        // filter can select them.
+        assert_eq!(
+            kinds(&opts(&["--prefix", "198.51.100.0/24"]), &updates),
+            [(Kind::Withdraw, "198.51.100.0/24".into(), None, None)]
+        );
+        assert!(kinds(&opts(&["64500"]), &updates).is_empty());
+    }
+}
@@ -0,0 +1,786 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+#[cfg(feature = "object-store")]
+use base64::engine::general_purpose::STANDARD;
+#[cfg(feature = "object-store")]
+use base64::Engine;
+#[cfg(feature = "object-store")]
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use structopt::StructOpt;
+
+use crate::delegated;
+use crate::http_cache::{self, HttpCache};
+#[cfg(feature = "object-store")]
+use crate::object_store;
+
+/// Hosts serving the same RouteViews archive tree, tried in order.
+const ROUTEVIEWS_MIRRORS: &[&str] = &["http://archive.routeviews.org", "http://routeviews.org"];
+const RIS_ARCHIVE: &str = "https://data.ris.ripe.net";
+
+/// Attempts per mirror before moving on to the next one.
+const ATTEMPTS: u32 = 4;
+/// Delay before the first retry, doubled after each further failure.
+const BACKOFF: Duration = Duration::from_secs(2);
+
+#[derive(StructOpt, Debug)]
+pub struct FetchOpts {
+    /// RouteViews collector, e.g. route-views2, route-views.sg, or RIPE RIS collector, e.g. rrc00
+    #[structopt(long)]
+    collector: String,
+
+    /// Collector to fetch from when no mirror serves the dump of --collector, e.g.
+    /// rrc00 for a RouteViews collector; tried in order
+    #[structopt(long, number_of_values = 1)]
+    fallback_collector: Vec<String>,
+
+    /// Dump time as YYYY-MM-DDTHH:MM (UTC), rounded down to the collector's RIB
+    /// schedule: two-hourly for RouteViews, eight-hourly for RIS
+    #[structopt(long, parse(try_from_str = parse_datetime))]
+    date: (u32, u32),
+
+    /// Smallest plausible dump in bytes; smaller downloads are quarantined as
+    /// truncated or as error pages
+    #[structopt(long, default_value = "1048576")]
+    min_size: u64,
+
+    /// Directory for downloaded dumps [default: ~/.cache/bgptools/ribs]
+    #[structopt(long, parse(from_os_str))]
+    cache_dir: Option<PathBuf>,
+}
+
+/// An HTTP request for a download, with the headers that authorize it.
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+}
+
+/// `YYYY-MM-DDTHH:MM` as (`YYYYMMDD`, `HHMM`).
+fn parse_datetime(s: &str) -> Result<(u32, u32), String> {
+    let (date, time) = s
+        .split_once('T')
+        .ok_or_else(|| format!("{}: expected YYYY-MM-DDTHH:MM", s))?;
+    let (hour, minute) = time
+        .split_once(':')
+        .ok_or_else(|| format!("{}: expected YYYY-MM-DDTHH:MM", s))?;
+    let hour: u32 = hour.parse().map_err(|e| format!("{}: {}", s, e))?;
+    let minute: u32 = minute.parse().map_err(|e| format!("{}: {}", s, e))?;
+    if hour > 23 || minute > 59 {
+        return Err(format!("{}: time out of range", s));
+    }
+    Ok((delegated::parse_date(date)?, hour * 100 + minute))
+}
+
+/// File name and mirror URLs of the RIB dump a collector made at or before
+/// `time` on `date`. RIS collectors are named `rrcNN`; any other name is taken
+/// as a RouteViews collector.
+fn dump(collector: &str, date: u32, time: u32) -> (String, Vec<String>) {
+    let month = format!("{}.{:02}", date / 10000, date / 100 % 100);
+    if collector.starts_with("rrc") {
+        let name = format!("bview.{}.{:04}.gz", date, time / 800 * 800);
+        let url = format!("{}/{}/{}/{}", RIS_ARCHIVE, collector, month, name);
+        return (name, vec![url]);
+    }
+    let name = format!("rib.{}.{:04}.bz2", date, time / 200 * 200);
+    // route-views2 predates the per-collector layout and sits at the archive root.
+    let collector_dir = match collector {
+        "route-views2" => String::new(),
+        collector => format!("/{}", collector),
+    };
+    let urls = (ROUTEVIEWS_MIRRORS.iter())
+        .map(|mirror| {
+            format!(
+                "{}{}/bgpdata/{}/RIBS/{}",
+                mirror, collector_dir, month, name
+            )
+        })
+        .collect();
+    (name, urls)
+}
+
+/// Downloads the RIB dump of a collector into the cache, unless it is already
+/// there, and decompresses it to `mrt_file` for the other commands. When no
+/// mirror of the collector serves it, the fallback collectors are tried.
+pub fn run(mrt_file: &Path, opts: FetchOpts) {
+    let (date, time) = opts.date;
+    let cache_dir = opts.cache_dir.unwrap_or_else(default_cache_dir);
+    let collectors = std::iter::once(&opts.collector).chain(&opts.fallback_collector);
+    for collector in collectors {
+        let (name, urls) = dump(collector, date, time);
+        let cached = cache_dir.join(collector).join(&name);
+        let mirrors: Vec<Request> = (urls.into_iter())
+            .map(|url| Request {
+                url,
+                headers: Vec::new(),
+            })
+            .collect();
+        let url = match download(&mirrors, &cached, opts.min_size) {
+            Ok(url) => url,
+            Err(e) => {
+                eprintln!("warning: {}: {}", collector, e);
+                continue;
+            }
+        };
+        if let Err(e) = unpack_cached(&cached, mrt_file) {
+            eprintln!("warning: {}", e);
+            continue;
+        }
+        if let Err(e) = fs::write(source_path(mrt_file), &url) {
+            eprintln!("error: {}: {}", mrt_file.display(), e);
+            process::exit(1);
+        }
+        eprintln!("wrote {} from {}", mrt_file.display(), url);
+        return;
+    }
+    eprintln!("error: no collector served the dump");
+    process::exit(1);
+}
+
+/// Turns an `http(s)://`, `s3://` or `gs://` MRT source into a decompressed
+/// local copy in the download cache; local paths are returned unchanged.
+pub fn resolve(mrt_file: &Path) -> PathBuf {
+    match mrt_file.to_str() {
+        Some(uri) if is_remote(uri) => resolve_mirrors(&[uri]),
+        _ => mrt_file.to_path_buf(),
+    }
+}
+
+fn is_remote(uri: &str) -> bool {
+    ["http://", "https://", "s3://", "gs://"]
+        .iter()
+        .any(|scheme| uri.starts_with(scheme))
+}
+
+/// Like `resolve` for a dump that several URLs serve, tried in order.
+pub fn resolve_mirrors(uris: &[&str]) -> PathBuf {
+    let requests: Vec<Request> = (uris.iter())
+        .map(|uri| {
+            remote_request(uri).unwrap_or_else(|e| {
+                eprintln!("error: {}: {}", uri, e);
+                process::exit(2);
+            })
+        })
+        .collect();
+    let name = uris[0]
+        .rsplit('/')
+        .next()
+        .filter(|n| !n.is_empty())
+        .unwrap_or("rib");
+    let cached = default_cache_dir()
+        .join("url")
+        .join(format!("{:x}", Sha256::digest(uris.join("\n").as_bytes())))
+        .join(name);
+    let url = match download(&requests, &cached, 1) {
+        Ok(url) => url,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    };
+    let local = match cached.extension().and_then(|e| e.to_str()) {
+        Some("bz2" | "gz") => cached.with_extension(""),
+        _ => return cached,
+    };
+    if !local.exists() {
+        if let Err(e) = unpack_cached(&cached, &local) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+        if let Err(e) = fs::write(source_path(&local), url) {
+            eprintln!("error: {}: {}", local.display(), e);
+            process::exit(1);
+        }
+    }
+    local
+}
+
+/// The request for an http(s) URL, or for an object store URI when built with
+/// the object-store feature.
+fn remote_request(uri: &str) -> Result<Request, String> {
+    if uri.starts_with("http://") || uri.starts_with("https://") {
+        return Ok(Request {
+            url: uri.to_string(),
+            headers: Vec::new(),
+        });
+    }
+    #[cfg(feature = "object-store")]
+    return object_store::request(uri)
+        .ok_or_else(|| "expected s3://bucket/key or gs://bucket/key".to_string());
+    #[cfg(not(feature = "object-store"))]
+    Err("built without the object-store feature".to_string())
+}
+
+/// `~/.cache/bgptools/ribs`, next to the HTTP response cache.
+fn default_cache_dir() -> PathBuf {
+    HttpCache::default_dir().with_file_name("ribs")
+}
+
+/// Downloads `dest` from the first of `mirrors` that serves it, unless a
+/// previous run already did, and returns the URL it came from. The URL is kept
+/// next to the file, see `served_by`. Downloads that are truncated, fail an
+/// announced checksum, are smaller than `min_size` bytes or do not decompress
+/// are quarantined and the next mirror tried.
+fn download(mirrors: &[Request], dest: &Path, min_size: u64) -> Result<String, String> {
+    download_with_backoff(mirrors, dest, min_size, BACKOFF)
+}
+
+fn download_with_backoff(
+    mirrors: &[Request],
+    dest: &Path,
+    min_size: u64,
+    backoff: Duration,
+) -> Result<String, String> {
+    http_cache::record(dest.exists());
+    if dest.exists() {
+        // Files cached before mirrors were recorded had a single possible source.
+        return Ok(served_by(dest).unwrap_or_else(|| mirrors[0].url.clone()));
+    }
+    let dir = dest.parent().unwrap();
+    fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let partial = partial_path(dest);
+    let mut errors = Vec::new();
+    for request in mirrors {
+        let result = get_with_retry(request, &partial, backoff)
+            .and_then(|()| verify(&partial, dest, min_size));
+        let e = match result {
+            Ok(()) => {
+                fs::rename(&partial, dest).map_err(|e| format!("{}: {}", dest.display(), e))?;
+                fs::write(source_path(dest), &request.url)
+                    .map_err(|e| format!("{}: {}", dest.display(), e))?;
+                return Ok(request.url.clone());
+            }
+            Err(Failure::Corrupt(e)) => {
+                let quarantined = quarantine(&partial, dest);
+                format!("{}; quarantined as {}", e, quarantined.display())
+            }
+            Err(Failure::Transient(e) | Failure::Permanent(e)) => e,
+        };
+        eprintln!("warning: {}: {}", request.url, e);
+        errors.push(e);
+    }
+    Err(format!(
+        "no mirror served {}: {}",
+        dest.file_name().unwrap().to_string_lossy(),
+        errors.join("; ")
+    ))
+}
+
+/// Why an attempt at a download failed, which decides what is tried next.
+enum Failure {
+    /// Worth retrying from the same mirror.
+    Transient(String),
+    /// Not worth retrying; the next mirror may still have the file.
+    Permanent(String),
+    /// The file arrived but is not a complete dump; it is quarantined and the
+    /// next mirror tried.
+    Corrupt(String),
+}
+
+/// Fetches `request` into `partial`, retrying transient failures with
+/// exponential backoff: transport errors, 408, 429 and 5xx responses, and
+/// connections dropped mid-body. Other errors are returned at once.
+fn get_with_retry(request: &Request, partial: &Path, backoff: Duration) -> Result<(), Failure> {
+    let mut delay = backoff;
+    for attempt in 1.. {
+        match get(request, partial) {
+            Err(Failure::Transient(e)) if attempt < ATTEMPTS => {
+                eprintln!("warning: {}: {}; retrying in {:?}", request.url, e, delay);
+                thread::sleep(delay);
+                delay *= 2;
+            }
+            result => return result,
+        }
+    }
+    unreachable!()
+}
+
+/// One attempt at `request`. The body is checked against the length the
+/// server announces and, with the object-store feature, the MD5 digest, when
+/// they are announced for the bytes as sent rather than for a
+/// `Content-Encoding` of them.
+fn get(request: &Request, partial: &Path) -> Result<(), Failure> {
+    eprintln!("fetching {}", request.url);
+    let mut call = ureq::get(&request.url);
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+    let response = call.call().map_err(|e| match &e {
+        ureq::Error::Status(408 | 429 | 500..=599, _) | ureq::Error::Transport(_) => {
+            Failure::Transient(e.to_string())
+        }
+        ureq::Error::Status(..) => Failure::Permanent(e.to_string()),
+    })?;
+    let encoded = response.header("Content-Encoding").is_some();
+    let expected_len = (response.header("Content-Length"))
+        .filter(|_| !encoded)
+        .and_then(|n| n.parse::<u64>().ok());
+    #[cfg(feature = "object-store")]
+    let expected_md5 = announced_md5(&response).filter(|_| !encoded);
+    let file = File::create(partial)
+        .map_err(|e| Failure::Permanent(format!("{}: {}", partial.display(), e)))?;
+    let mut writer = Digesting {
+        inner: file,
+        #[cfg(feature = "object-store")]
+        md5: Md5::new(),
+        len: 0,
+    };
+    io::copy(&mut response.into_reader(), &mut writer)
+        .map_err(|e| Failure::Transient(e.to_string()))?;
+    if let Some(expected) = expected_len.filter(|&n| n != writer.len) {
+        return Err(Failure::Corrupt(format!(
+            "received {} of {} bytes",
+            writer.len, expected
+        )));
+    }
+    #[cfg(feature = "object-store")]
+    if expected_md5.is_some_and(|md5| md5[..] != writer.md5.finalize()[..]) {
+        return Err(Failure::Corrupt("MD5 digest mismatch".to_string()));
+    }
+    Ok(())
+}
+
+/// The MD5 digest of the body, from `Content-MD5`, from the `md5=` part of
+/// GCS's `x-goog-hash`, or from an S3 ETag, which is the hex MD5 of objects
+/// not uploaded in parts. Other servers' ETags are opaque and not used.
+#[cfg(feature = "object-store")]
+fn announced_md5(response: &ureq::Response) -> Option<[u8; 16]> {
+    let base64 = |s: &str| -> Option<[u8; 16]> { STANDARD.decode(s.trim()).ok()?.try_into().ok() };
+    if let Some(md5) = response.header("Content-MD5") {
+        return base64(md5);
+    }
+    if let Some(hashes) = response.header("x-goog-hash") {
+        return (hashes.split(','))
+            .find_map(|h| h.trim().strip_prefix("md5="))
+            .and_then(base64);
+    }
+    let etag = response.header("ETag")?.trim_matches('"');
+    if response.header("x-amz-request-id").is_none() || etag.len() != 32 {
+        return None;
+    }
+    let mut md5 = [0u8; 16];
+    for (i, byte) in md5.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(etag.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(md5)
+}
+
+/// Writes through to `inner`, keeping the length and, with the object-store
+/// feature, the MD5 digest of what passed.
+struct Digesting<W> {
+    inner: W,
+    #[cfg(feature = "object-store")]
+    md5: Md5,
+    len: u64,
+}
+
+impl<W: Write> Write for Digesting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        #[cfg(feature = "object-store")]
+        self.md5.update(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Rejects a download to be named `dest` that is smaller than `min_size` or
+/// whose bzip2 or gzip stream does not decode to the end, as left by an
+/// archive serving a dump still being written.
+fn verify(partial: &Path, dest: &Path, min_size: u64) -> Result<(), Failure> {
+    let len = fs::metadata(partial)
+        .map_err(|e| Failure::Permanent(format!("{}: {}", partial.display(), e)))?
+        .len();
+    if len < min_size {
+        return Err(Failure::Corrupt(format!(
+            "{} bytes, fewer than the {} expected of a dump",
+            len, min_size
+        )));
+    }
+    let file = File::open(partial)
+        .map_err(|e| Failure::Permanent(format!("{}: {}", partial.display(), e)))?;
+    io::copy(&mut decoder(file, dest), &mut io::sink())
+        .map_err(|e| Failure::Corrupt(format!("corrupt compressed stream: {}", e)))?;
+    Ok(())
+}
+
+/// Moves a failed download aside as `<dest>.quarantined` for inspection, so
+/// that it is neither used nor mistaken for a finished download, and returns
+/// where it went.
+fn quarantine(file: &Path, dest: &Path) -> PathBuf {
+    let quarantined = with_suffix(dest, ".quarantined");
+    if let Err(e) = fs::rename(file, &quarantined) {
+        eprintln!("warning: {}: {}", file.display(), e);
+    }
+    let _ = fs::remove_file(source_path(dest));
+    quarantined
+}
+
+/// The URL `path` was downloaded from, for files this module put in place.
+pub fn served_by(path: &Path) -> Option<String> {
+    path.file_name()?;
+    fs::read_to_string(source_path(path)).ok()
+}
+
+/// `path` with `.part` appended, where it is written before being renamed into
+/// place, so that an interrupted run never leaves a truncated file behind.
+fn partial_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".part")
+}
+
+/// `path` with `.source` appended, holding the URL the file came from.
+fn source_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".source")
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// Unpacks a file of the download cache. Fresh downloads were checked to
+/// decompress, but a cached one may have rotted since; if it is what failed,
+/// it is quarantined, so that the next run downloads it anew.
+fn unpack_cached(cached: &Path, dst: &Path) -> Result<(), String> {
+    unpack(cached, dst).map_err(|e| match verify(cached, cached, 0) {
+        Err(Failure::Corrupt(reason)) => format!(
+            "{}: {}; quarantined as {}",
+            cached.display(),
+            reason,
+            quarantine(cached, cached).display()
+        ),
+        _ => format!("{}: {}", dst.display(), e),
+    })
+}
+
+/// Decompresses `src` to `dst` by way of a partial file.
+pub fn unpack(src: &Path, dst: &Path) -> io::Result<()> {
+    let partial = partial_path(dst);
+    let mut reader = decoder(File::open(src)?, src);
+    io::copy(&mut reader, &mut File::create(&partial)?)?;
+    fs::rename(&partial, dst)
+}
+
+/// `file` decompressed according to the extension of `name`.
+fn decoder(file: File, name: &Path) -> Box<dyn Read> {
+    match name.extension().and_then(|e| e.to_str()) {
+        Some("bz2") => Box::new(bzip2::read::BzDecoder::new(file)),
+        Some("gz") => Box::new(flate2::read::MultiGzDecoder::new(file)),
+        _ => Box::new(file),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    /// Serves `responses` to one connection each, in order, and returns the
+    /// URL of `path` on the server and a handle yielding the request lines.
+    fn serve(
+        responses: &[impl AsRef<str>],
+        path: &str,
+    ) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}{}", listener.local_addr().unwrap(), path);
+        let responses: Vec<String> = responses.iter().map(|r| r.as_ref().to_string()).collect();
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                requests.push(line.trim_end().to_string());
+                while line != "\r\n" {
+                    line.clear();
+                    reader.read_line(&mut line).unwrap();
+                }
+                let body = if response.starts_with("HTTP/1.1 200") {
+                    "TABLE_DUMP2"
+                } else {
+                    ""
+                };
+                write!(
+                    reader.get_mut(),
+                    "{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    fn request(url: &str) -> Request {
+        Request {
+            url: url.to_string(),
+            headers: Vec::new(),
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("bgptools-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn gzip(path: &Path, content: &[u8]) {
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn transient_errors_are_retried() {
+        let dir = temp_dir("retry");
+        let dest = dir.join("rib");
+        let (url, server) = serve(
+            &[
+                "HTTP/1.1 503 Service Unavailable",
+                "HTTP/1.1 429 Too Many Requests",
+                "HTTP/1.1 200 OK",
+            ],
+            "/rib",
+        );
+        let served = download_with_backoff(&[request(&url)], &dest, 1, Duration::from_millis(1));
+        assert_eq!(served, Ok(url.clone()));
+        assert_eq!(server.join().unwrap().len(), 3);
+        assert_eq!(fs::read(&dest).unwrap(), b"TABLE_DUMP2");
+        assert_eq!(served_by(&dest), Some(url.clone()));
+        // A cache hit reports the mirror of the original download.
+        let other = request("http://127.0.0.1:9/rib");
+        assert_eq!(
+            download_with_backoff(&[other], &dest, 1, Duration::ZERO),
+            Ok(url)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_files_fall_back_to_the_next_mirror() {
+        let dir = temp_dir("mirror");
+        let dest = dir.join("rib");
+        let (missing, first) = serve(&["HTTP/1.1 404 Not Found"], "/rib");
+        let (mirror, second) = serve(&["HTTP/1.1 200 OK"], "/mirror/rib");
+        let served = download_with_backoff(
+            &[request(&missing), request(&mirror)],
+            &dest,
+            1,
+            Duration::from_millis(1),
+        );
+        assert_eq!(served, Ok(mirror));
+        assert_eq!(first.join().unwrap(), ["GET /rib HTTP/1.1"]);
+        assert_eq!(second.join().unwrap(), ["GET /mirror/rib HTTP/1.1"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn exhausted_mirrors_leave_nothing_behind() {
+        let dir = temp_dir("exhausted");
+        let dest = dir.join("rib");
+        let failures = ["HTTP/1.1 502 Bad Gateway"; ATTEMPTS as usize];
+        let (url, server) = serve(&failures, "/rib");
+        let served = download_with_backoff(&[request(&url)], &dest, 1, Duration::from_millis(1));
+        assert!(served.unwrap_err().starts_with("no mirror served rib: "));
+        assert_eq!(server.join().unwrap().len(), ATTEMPTS as usize);
+        assert!(!dest.exists());
+        assert!(served_by(&dest).is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Downloads `name` from a mirror answering `response`, with the fixture
+    /// dump as its body, then from a second mirror answering plainly, and
+    /// returns which mirror served it, if any, and whether a file was
+    /// quarantined.
+    fn download_after(response: String, name: &str, min_size: u64) -> (&'static str, bool) {
+        let dir = temp_dir(&format!("verify-{}", name));
+        let (first, first_server) = serve(&[response], "/first");
+        let (second, second_server) = serve(&["HTTP/1.1 200 OK"], "/second");
+        let dest = dir.join(name);
+        let served = download_with_backoff(
+            &[request(&first), request(&second)],
+            &dest,
+            min_size,
+            Duration::from_millis(1),
+        );
+        first_server.join().unwrap();
+        let mirror = match served {
+            Ok(url) if url == first => "first",
+            Ok(_) => "second",
+            Err(_) => "none",
+        };
+        if mirror != "first" {
+            second_server.join().unwrap();
+        }
+        let quarantined = with_suffix(&dest, ".quarantined").exists();
+        fs::remove_dir_all(&dir).unwrap();
+        (mirror, quarantined)
+    }
+
+    fn assert_downloads(cases: &[(String, &str, u64, &str)]) {
+        for (response, name, min_size, mirror) in cases {
+            let quarantined = *mirror != "first";
+            assert_eq!(
+                download_after(response.clone(), name, *min_size),
+                (*mirror, quarantined),
+                "{} {}",
+                name,
+                response
+            );
+        }
+    }
+
+    #[test]
+    fn downloads_failing_checks_are_quarantined() {
+        let ok = "HTTP/1.1 200 OK";
+        assert_downloads(&[
+            (ok.to_string(), "rib", 11, "first"),
+            // Both mirrors serve the same 11 bytes, which is not a gzip stream either.
+            (ok.to_string(), "rib", 12, "none"),
+            (ok.to_string(), "rib.gz", 1, "none"),
+            ("HTTP/1.1 204 No Content".to_string(), "rib", 1, "second"),
+        ]);
+    }
+
+    #[cfg(feature = "object-store")]
+    #[test]
+    fn announced_digests_are_checked() {
+        let md5 = Md5::digest(b"TABLE_DUMP2");
+        let hex: String = md5.iter().map(|b| format!("{:02x}", b)).collect();
+        let ok = "HTTP/1.1 200 OK";
+        assert_downloads(&[
+            (
+                format!("{}\r\nContent-MD5: {}", ok, STANDARD.encode(md5)),
+                "rib",
+                1,
+                "first",
+            ),
+            (
+                format!("{}\r\nContent-MD5: {}", ok, STANDARD.encode([0; 16])),
+                "rib",
+                1,
+                "second",
+            ),
+            (
+                format!(
+                    "{}\r\nx-goog-hash: crc32c=AAAAAA==, md5={}",
+                    ok,
+                    STANDARD.encode(md5)
+                ),
+                "rib",
+                1,
+                "first",
+            ),
+            (
+                format!(
+                    "{}\r\nx-goog-hash: crc32c=AAAAAA==, md5={}",
+                    ok,
+                    STANDARD.encode([0; 16])
+                ),
+                "rib",
+                1,
+                "second",
+            ),
+            (
+                format!("{}\r\nx-amz-request-id: 1\r\nETag: \"{}\"", ok, hex),
+                "rib",
+                1,
+                "first",
+            ),
+            (
+                format!(
+                    "{}\r\nx-amz-request-id: 1\r\nETag: \"{}\"",
+                    ok,
+                    "0".repeat(32)
+                ),
+                "rib",
+                1,
+                "second",
+            ),
+            // Other servers' ETags are opaque.
+            (
+                format!("{}\r\nETag: \"{}\"", ok, "0".repeat(32)),
+                "rib",
+                1,
+                "first",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn rotten_cache_entries_are_quarantined() {
+        let dir = temp_dir("rotten");
+        fs::create_dir_all(&dir).unwrap();
+        let cached = dir.join("rib.gz");
+        gzip(&cached, b"TABLE_DUMP2");
+        // A destination that cannot be written leaves a sound cached file alone.
+        let unwritable = dir.join("missing").join("rib");
+        assert!(unpack_cached(&cached, &unwritable).is_err());
+        assert!(cached.exists());
+        fs::write(&cached, b"not gzip").unwrap();
+        fs::write(source_path(&cached), "http://example.com/rib.gz").unwrap();
+        assert!(unpack_cached(&cached, &dir.join("rib")).is_err());
+        assert!(!cached.exists());
+        assert!(served_by(&cached).is_none());
+        assert_eq!(
+            fs::read(dir.join("rib.gz.quarantined")).unwrap(),
+            b"not gzip"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn collectors_map_to_their_archives() {
+        let (name, urls) = dump("route-views.sg", 20240601, 359);
+        assert_eq!(name, "rib.20240601.0200.bz2");
+        assert_eq!(
+            urls,
+            [
+                "http://archive.routeviews.org/route-views.sg/bgpdata/2024.06/RIBS/rib.20240601.0200.bz2",
+                "http://routeviews.org/route-views.sg/bgpdata/2024.06/RIBS/rib.20240601.0200.bz2",
+            ]
+        );
+        assert_eq!(
+            dump("route-views2", 20240601, 0).1[0],
+            "http://archive.routeviews.org/bgpdata/2024.06/RIBS/rib.20240601.0000.bz2"
+        );
+        assert_eq!(
+            dump("rrc00", 20240601, 2359),
+            (
+                "bview.20240601.1600.gz".to_string(),
+                vec!["https://data.ris.ripe.net/rrc00/2024.06/bview.20240601.1600.gz".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn unpack_renames_into_place() {
+        let dir = temp_dir("unpack");
+        fs::create_dir_all(&dir).unwrap();
+        let src = dir.join("rib.gz");
+        gzip(&src, b"TABLE_DUMP2");
+        let dst = dir.join("rib");
+        unpack(&src, &dst).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"TABLE_DUMP2");
+        assert!(!partial_path(&dst).exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::rib;
+
+#[derive(StructOpt, Debug)]
+pub struct HegemonyOpts {
+    /// Only print the N most central transit ASNs per target
+    #[structopt(long)]
+    top: Option<usize>,
+
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+#[derive(Default)]
+struct Counts {
+    paths: u64,
+    transit: HashMap<u32, u64>,
+}
+
+/// Scores each transit AS by the fraction of observed paths toward a target ASN
+/// that traverse it. As in IHR's AS hegemony, the origin and the AS hosting the
+/// vantage point are left out, since every path trivially contains both.
+pub fn run(mrt_file: &Path, opts: HegemonyOpts) {
+    let mut counts: HashMap<u32, Counts> = opts
+        .asns
+        .iter()
+        .map(|&asn| (asn, Counts::default()))
+        .collect();
+    let mut hops: Vec<u32> = Vec::new();
+    rib::for_each_route(mrt_file, |route| {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        let counts = match counts.get_mut(&origin) {
+            Some(counts) => counts,
+            None => return,
+        };
+        counts.paths += 1;
+        let peer = route.peer_asn().unwrap_or(origin);
+        hops.clear();
+        hops.extend(
+            route
+                .as_path
+                .iter()
+                .copied()
+                .filter(|&asn| asn != origin && asn != peer),
+        );
+        hops.sort_unstable();
+        hops.dedup();
+        for &asn in &hops {
+            *counts.transit.entry(asn).or_default() += 1;
+        }
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for asn in &opts.asns {
+        let counts = &counts[asn];
+        let mut scores: Vec<(u32, f64)> = counts
+            .transit
+            .iter()
+            .map(|(&transit, &n)| (transit, n as f64 / counts.paths as f64))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(opts.top.unwrap_or(scores.len()));
+        for (transit, score) in scores {
+            writeln!(out, "AS{}\tAS{}\t{:.4}", asn, transit, score).unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
@@ -0,0 +1,140 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+/// Lookups served from disk and lookups that went to the network, over this
+/// cache and the download cache of `fetch` during the run.
+#[derive(Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Counts a lookup as a hit or a miss.
+pub fn record(hit: bool) {
+    let counter = if hit { &HITS } else { &MISSES };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn stats() -> CacheStats {
+    CacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+    }
+}
+
+/// Disk-backed cache for online lookups.
+///
+/// Fresh entries are served without touching the network, requests that do go out
+/// are spaced at least `min_interval` apart, and a failed request falls back to a
+/// stale entry when there is one.
+pub struct HttpCache {
+    dir: PathBuf,
+    ttl: Duration,
+    min_interval: Duration,
+    offline: bool,
+    last_request: Option<Instant>,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf, ttl: Duration, min_interval: Duration, offline: bool) -> Self {
+        HttpCache {
+            dir,
+            ttl,
+            min_interval,
+            offline,
+            last_request: None,
+        }
+    }
+
+    /// `$XDG_CACHE_HOME/bgptools/http`, falling back to `~/.cache/bgptools/http`,
+    /// or `%LOCALAPPDATA%\bgptools\http` on Windows.
+    pub fn default_dir() -> PathBuf {
+        let base = if cfg!(windows) {
+            "LOCALAPPDATA"
+        } else {
+            "XDG_CACHE_HOME"
+        };
+        env::var_os(base)
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .unwrap_or_else(env::temp_dir)
+            .join("bgptools")
+            .join("http")
+    }
+
+    pub fn get(&mut self, url: &str) -> Result<String, String> {
+        let path = self
+            .dir
+            .join(format!("{:x}", Sha256::digest(url.as_bytes())));
+        let age = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+        if self.offline || age.is_some_and(|age| age <= self.ttl) {
+            let cached = fs::read_to_string(&path);
+            record(cached.is_ok());
+            return cached.map_err(|e| format!("{}: not cached ({})", url, e));
+        }
+        record(false);
+
+        match self.fetch(url) {
+            Ok(body) => {
+                fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+                fs::write(&path, &body).map_err(|e| e.to_string())?;
+                Ok(body)
+            }
+            Err(e) if age.is_some() => {
+                eprintln!("warning: {}; using stale cached copy", e);
+                fs::read_to_string(&path).map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn fetch(&mut self, url: &str) -> Result<String, String> {
+        if let Some(last) = self.last_request {
+            let elapsed = last.elapsed();
+            if elapsed < self.min_interval {
+                thread::sleep(self.min_interval - elapsed);
+            }
+        }
+        self.last_request = Some(Instant::now());
+        ureq::get(url)
+            .call()
+            .map_err(|e| e.to_string())?
+            .into_string()
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    #[test]
+    fn offline_lookups_count_as_hits_and_misses() {
+        let dir = env::temp_dir().join(format!("bgptools-test-http-cache-{}", process::id()));
+        let url = "https://example.com/data.json";
+        let mut cache = HttpCache::new(dir.clone(), Duration::ZERO, Duration::ZERO, true);
+        let before = stats();
+        assert!(cache.get(url).is_err());
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!("{:x}", Sha256::digest(url))), "{}").unwrap();
+        assert_eq!(cache.get(url).unwrap(), "{}");
+        let after = stats();
+        assert_eq!(after.hits - before.hits, 1);
+        assert_eq!(after.misses - before.misses, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use structopt::StructOpt;
+
+use crate::archive;
+use crate::fetch;
+use crate::peers::{self, Counts};
+use crate::prefix::Prefix;
+use crate::rib::{self, Peer};
+
+#[derive(StructOpt, Debug)]
+pub struct InspectOpts {
+    /// RIB file to inspect instead of --mrt-file
+    #[structopt(parse(from_os_str))]
+    file: Option<PathBuf>,
+}
+
+#[derive(Default)]
+struct Family {
+    entries: u64,
+    prefixes: HashSet<Prefix>,
+}
+
+/// Reports what a dump contains: its timestamp, the collector peers and their
+/// ASNs, and RIB entries and distinct prefixes per address family, followed by
+/// the per-peer table of the peers subcommand.
+pub fn run(mrt_file: &Path, opts: InspectOpts) {
+    let path = match &opts.file {
+        Some(file) => fetch::resolve(file),
+        None => mrt_file.to_path_buf(),
+    };
+    let files = rib::expand(&path);
+    let mut v4 = Family::default();
+    let mut v6 = Family::default();
+    let mut peers: BTreeMap<Peer, Counts> = BTreeMap::new();
+    let dump_time = rib::for_each_route(&path, |route| {
+        let family = if route.prefix.is_ipv4() {
+            &mut v4
+        } else {
+            &mut v6
+        };
+        family.entries += 1;
+        family.prefixes.insert(route.prefix);
+        if let Some(peer) = route.peer {
+            peers.entry(peer).or_default().add(route.prefix);
+        }
+    });
+    let peer_asns: BTreeSet<u32> = peers.keys().map(|p| p.asn).collect();
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    writeln!(out, "files\t{}", files.len()).unwrap();
+    let dump_time = match dump_time {
+        0 => "unknown".to_string(),
+        time => format_time(time),
+    };
+    writeln!(out, "dump_time\t{}", dump_time).unwrap();
+    writeln!(out, "peers\t{}", peers.len()).unwrap();
+    let peer_asns: Vec<String> = peer_asns.iter().map(|a| format!("AS{}", a)).collect();
+    writeln!(out, "peer_asns\t{}", peer_asns.join(",")).unwrap();
+    for (name, family) in [("ipv4", &v4), ("ipv6", &v6)] {
+        writeln!(out, "{}_entries\t{}", name, family.entries).unwrap();
+        writeln!(out, "{}_prefixes\t{}", name, family.prefixes.len()).unwrap();
+    }
+    if !peers.is_empty() {
+        writeln!(out).unwrap();
+        peers::write_table(&mut out, &peers).unwrap();
+    }
+    out.flush().unwrap();
+}
+
+/// `YYYY-MM-DDTHH:MM:SSZ` for a Unix timestamp.
+fn format_time(time: u32) -> String {
+    let (year, month, day) = archive::civil_from_days(time / 86400);
+    let seconds = time % 86400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
@@ -0,0 +1,81 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::output::Entry;
+use crate::prefix::Prefix;
+use crate::rib;
+
+#[derive(StructOpt, Debug)]
+pub struct LengthsOpts {
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+/// Prints how many distinct prefixes of each length every ASN originates, to
+/// spot deaggregation before it reaches a generated list.
+pub fn run(mrt_file: &Path, opts: LengthsOpts) {
+    let mut prefixes: HashMap<u32, HashSet<Prefix>> =
+        opts.asns.iter().map(|&asn| (asn, HashSet::new())).collect();
+    rib::for_each_route(mrt_file, |route| {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some(prefixes) = prefixes.get_mut(&origin) {
+            prefixes.insert(route.prefix);
+        }
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for asn in &opts.asns {
+        let mut histogram: BTreeMap<(bool, u8), usize> = BTreeMap::new();
+        for prefix in &prefixes[asn] {
+            *histogram
+                .entry((!prefix.is_ipv4(), prefix.len))
+                .or_default() += 1;
+        }
+        for ((v6, len), count) in histogram {
+            let family = if v6 { "ipv6" } else { "ipv4" };
+            writeln!(out, "AS{}\t{}\t/{}\t{}", asn, family, len, count).unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
+
+/// Drops the prefixes of every ASN originating more than `limit` prefixes of a
+/// single length, warning about each. Prefixes also originated by another ASN
+/// are kept for that ASN.
+pub fn enforce_limit(entries: &mut Vec<Entry>, limit: usize) {
+    let mut counts: HashMap<(u32, bool, u8), usize> = HashMap::new();
+    for entry in entries.iter() {
+        for &asn in &entry.origins {
+            *counts
+                .entry((asn, !entry.prefix.is_ipv4(), entry.prefix.len))
+                .or_default() += 1;
+        }
+    }
+    let mut over: Vec<(u32, bool, u8, usize)> = counts
+        .into_iter()
+        .filter(|&(_, count)| count > limit)
+        .map(|((asn, v6, len), count)| (asn, v6, len, count))
+        .collect();
+    if over.is_empty() {
+        return;
+    }
+    over.sort_unstable();
+    for (asn, v6, len, count) in &over {
+        let family = if *v6 { "IPv6" } else { "IPv4" };
+        eprintln!(
+            "warning: AS{} originates {} {} /{} prefixes, over --max-per-length {}; dropping its prefixes",
+            asn, count, family, len, limit
+        );
+    }
+    let dropped: HashSet<u32> = over.iter().map(|o| o.0).collect();
+    for entry in entries.iter_mut() {
+        entry.origins.retain(|asn| !dropped.contains(asn));
+    }
+    entries.retain(|e| !e.origins.is_empty());
+}
@@ -1,62 +1,284 @@
-use std::collections::HashSet;
-use std::fs::File;
+use std::env;
 use std::path::PathBuf;
+use std::process;
+use structopt::clap::{self, AppSettings, ArgMatches, ErrorKind};
 use structopt::StructOpt;
-use try_match::try_match;
-use what_i_want::*;
 
 extern crate mrt;
 
+mod archive;
+mod bgp4mp;
+mod bgpdump;
+#[cfg(unix)]
+mod bird;
+mod cisco;
+mod crosscheck;
+mod cymru;
+mod delegated;
+mod dualstack;
+mod events;
+mod fetch;
+mod hegemony;
+mod http_cache;
+mod inspect;
+mod lengths;
+mod neighbors;
+#[cfg(feature = "object-store")]
+mod object_store;
+mod output;
+mod overrides;
+mod paths_to;
+mod peers;
+mod pfx2as;
+mod poisoning;
+mod prefix;
+mod publish;
+mod resolve;
+mod rib;
+mod sample;
+mod script;
+mod setop;
+mod sink;
+mod size;
+mod summary;
+mod unmatched;
+mod verify;
+mod visibility;
+
 #[derive(StructOpt, Debug)]
-#[structopt(name = "bgptools")]
+#[structopt(
+    name = "bgptools",
+    setting = AppSettings::SubcommandsNegateReqs,
+    after_help = "Giving ASNs without a subcommand runs `resolve` and is kept for compatibility; \
+                  new scripts should spell out `bgptools resolve <asns>...`."
+)]
 struct Opts {
-    #[structopt(short, long, parse(from_os_str), default_value = "./rib")]
+    /// TABLE_DUMP_V2 RIB file, or a directory or glob of them (BGP4MP updates files for
+    /// events), or an http(s), s3:// or gs:// URI to download and cache
+    #[structopt(
+        short,
+        long,
+        global = true,
+        parse(from_os_str),
+        default_value = "./rib",
+        env = "BGPTOOLS_MRT_FILE"
+    )]
     mrt_file: PathBuf,
 
-    #[structopt(required = true, min_values = 1)]
-    asns: Vec<String>,
+    /// Another http(s), s3:// or gs:// URI serving the same dump as --mrt-file, tried in order
+    /// when downloading it fails
+    #[structopt(
+        long,
+        global = true,
+        number_of_values = 1,
+        env = "BGPTOOLS_MIRROR",
+        use_delimiter = true
+    )]
+    mirror: Vec<String>,
+
+    #[structopt(subcommand)]
+    cmd: Option<Command>,
+
+    /// Refuse to run when an unknown BGPTOOLS_* variable is set
+    #[structopt(long, global = true)]
+    strict_env: bool,
+
+    #[structopt(flatten)]
+    resolve: resolve::ResolveOpts,
 }
 
-macro_rules! match_or_continue {
-    ($in:expr, $(|)? $($p:pat_param)|+ $(if $guard:expr)? => $out:expr) => {
-        unwrap_or_continue!(try_match!($in, $($p)|+ $(if $guard)? => $out))
-    };
+// Parsed once per run, so the size of the resolve variant does not matter.
+#[allow(clippy::large_enum_variant)]
+#[derive(StructOpt, Debug)]
+enum Command {
+    /// Select the prefixes originated by the given ASNs (the default without a subcommand)
+    Resolve(resolve::ResolveOpts),
+    /// Count the prefixes each ASN originates per prefix length
+    Lengths(lengths::LengthsOpts),
+    /// Classify ASNs as v4-only, v6-only or dual-stack by what they originate
+    Dualstack(dualstack::DualstackOpts),
+    /// Download a RouteViews or RIPE RIS RIB dump to the MRT file path
+    Fetch(fetch::FetchOpts),
+    /// Write announcements, withdrawals and origin changes of an updates file as NDJSON
+    Events(events::EventsOpts),
+    /// Union, intersect or subtract two plain prefix lists
+    Setop(setop::SetopOpts),
+    /// Report the announced address space of each ASN
+    Size(size::SizeOpts),
+    /// Compare each ASN's prefixes with RIPEstat's announced-prefixes data
+    Crosscheck(crosscheck::CrosscheckOpts),
+    /// Score transit ASNs by the share of paths toward each ASN they carry
+    Hegemony(hegemony::HegemonyOpts),
+    /// List the collector peers of the dump and the routes learned from each
+    Peers(peers::PeersOpts),
+    /// List the AS paths toward the most specific prefix covering an address
+    PathsTo(paths_to::PathsToOpts),
+    /// Report how widely each ASN's prefixes are seen across peers
+    Visibility(visibility::VisibilityOpts),
+    /// Compare the ASNs' prefixes with a pinned list, failing beyond a tolerance
+    Verify(verify::VerifyOpts),
+    /// Summarize a dump's peers, per-family entry counts and timestamp
+    Inspect(inspect::InspectOpts),
+    /// List the ASNs seen next to each ASN in AS paths, upstream or downstream
+    Neighbors(neighbors::NeighborsOpts),
+}
 
-    ($in:expr, $(|)? $($p:pat_param)|+ $(if $guard:expr)?) => {
-        unwrap_or_continue!(try_match!($in, $($p)|+ $(if $guard)?))
-    };
+/// Environment variables read in place of the corresponding flags; a test
+/// keeps the list in step with the `env` of every flag.
+const ENV_VARS: &[&str] = &[
+    "BGPTOOLS_MRT_FILE",
+    "BGPTOOLS_MIRROR",
+    "BGPTOOLS_FORMAT",
+    "BGPTOOLS_ZONE",
+    "BGPTOOLS_SET_NAME",
+    "BGPTOOLS_NFT_TABLE",
+    "BGPTOOLS_MAXELEM",
+    "BGPTOOLS_CHAIN",
+    "BGPTOOLS_ACTION",
+    "BGPTOOLS_TABLE",
+    "BGPTOOLS_TABLE_FILE",
+    "BGPTOOLS_LIST_NAME",
+    "BGPTOOLS_HEADER_FILE",
+    "BGPTOOLS_FOOTER_FILE",
+    "BGPTOOLS_V6_STYLE",
+    "BGPTOOLS_LINE_ENDING",
+    "BGPTOOLS_OUTPUT",
+    "BGPTOOLS_OUTPUT_COMPRESS",
+    "BGPTOOLS_PREVIOUS",
+    "BGPTOOLS_VALIDITY",
+    "BGPTOOLS_MERGE_POLICY",
+    "BGPTOOLS_TOO_SPECIFIC",
+    "BGPTOOLS_MAX_PER_LENGTH",
+    "BGPTOOLS_OVERRIDES",
+    "BGPTOOLS_PUBLISH_DIR",
+    "BGPTOOLS_ARCHIVE_DIR",
+    "BGPTOOLS_RETENTION_DAYS",
+    "BGPTOOLS_SUMMARY",
+    "BGPTOOLS_ASNS",
+];
+
+/// The first resolve flag given before a subcommand, where it would otherwise
+/// be silently ignored.
+fn misplaced_flag(matches: &ArgMatches) -> Option<&'static str> {
+    matches.subcommand_name()?;
+    (resolve::FLAGS.iter().copied()).find(|&name| matches.occurrences_of(name) > 0)
 }
 
 fn main() {
-    let opts: Opts = Opts::from_args();
-    let asn_list: HashSet<u32> = opts
-        .asns
-        .into_iter()
-        .map(|x| x.parse::<u32>().expect("args(ASN) must be a number!"))
-        .collect();
-    let file = File::open(&opts.mrt_file).unwrap();
-    let entries = mrt::read_file_complete(file).unwrap();
-    for entry in &entries {
-        match_or_continue!(&entry.mrt_header.mrt_type, mrt::MrtType::TABLE_DUMP_V2);
-        let (header, entries) = match_or_continue!(
-            &entry.message,
-            mrt::MrtMessage::RIB_IPV4_UNICAST { header, entries }
-                | mrt::MrtMessage::RIB_IPV6_UNICAST { header, entries } => (header, entries)
-        );
-        for e in entries {
-            for a in &e.bgp_attributes {
-                let segments = match_or_continue!(
-                    &a.value,
-                    mrt::BgpAttributeValue::AS_PATH { segments } => segments
-                );
-                for s in segments {
-                    match_or_continue!(&s.segment_type, mrt::SegmentType::AS_SEQUENCE);
-                    let asn = unwrap_or_continue!(s.asns.last());
-                    if asn_list.contains(asn) {
-                        println!("{}/{}", header.prefix, header.prefix_length);
-                    }
-                }
-            }
+    let matches = Opts::clap().get_matches();
+    if let Some(name) = misplaced_flag(&matches) {
+        clap::Error::with_description(
+            &format!(
+                "--{} belongs to resolve and has no effect before the `{}` subcommand",
+                name,
+                matches.subcommand_name().unwrap_or_default()
+            ),
+            ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+    let opts = Opts::from_clap(&matches);
+    if opts.strict_env {
+        let unknown: Vec<String> = env::vars_os()
+            .filter_map(|(key, _)| key.into_string().ok())
+            .filter(|key| key.starts_with("BGPTOOLS_") && !ENV_VARS.contains(&key.as_str()))
+            .collect();
+        if !unknown.is_empty() {
+            eprintln!(
+                "error: unknown environment variables: {}",
+                unknown.join(", ")
+            );
+            process::exit(2);
+        }
+    }
+    let cmd = opts.cmd.unwrap_or(Command::Resolve(opts.resolve));
+    // fetch writes the MRT file rather than reading it, setop reads prefix
+    // lists only and --schema reads nothing.
+    let mrt_file = match &cmd {
+        Command::Fetch(_) | Command::Setop(_) => opts.mrt_file,
+        Command::Resolve(resolve_opts) if resolve_opts.schema => opts.mrt_file,
+        _ if opts.mirror.is_empty() => fetch::resolve(&opts.mrt_file),
+        _ => {
+            let mut uris = vec![opts.mrt_file.to_str().unwrap_or_default()];
+            uris.extend(opts.mirror.iter().map(String::as_str));
+            fetch::resolve_mirrors(&uris)
         }
+    };
+    match cmd {
+        Command::Resolve(resolve_opts) => resolve::run(&mrt_file, resolve_opts),
+        Command::Dualstack(dualstack_opts) => dualstack::run(&mrt_file, dualstack_opts),
+        Command::Events(events_opts) => events::run(&mrt_file, events_opts),
+        Command::Fetch(fetch_opts) => fetch::run(&mrt_file, fetch_opts),
+        Command::Lengths(lengths_opts) => lengths::run(&mrt_file, lengths_opts),
+        Command::Setop(setop_opts) => setop::run(setop_opts),
+        Command::Size(size_opts) => size::run(&mrt_file, size_opts),
+        Command::Crosscheck(crosscheck_opts) => crosscheck::run(&mrt_file, crosscheck_opts),
+        Command::Hegemony(hegemony_opts) => hegemony::run(&mrt_file, hegemony_opts),
+        Command::Peers(peers_opts) => peers::run(&mrt_file, peers_opts),
+        Command::PathsTo(paths_to_opts) => paths_to::run(&mrt_file, paths_to_opts),
+        Command::Visibility(visibility_opts) => visibility::run(&mrt_file, visibility_opts),
+        Command::Verify(verify_opts) => verify::run(&mrt_file, verify_opts),
+        Command::Inspect(inspect_opts) => inspect::run(&mrt_file, inspect_opts),
+        Command::Neighbors(neighbors_opts) => neighbors::run(&mrt_file, neighbors_opts),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn misplaced(args: &[&str]) -> Option<&'static str> {
+        let matches = Opts::clap()
+            .get_matches_from_safe(std::iter::once("bgptools").chain(args.iter().copied()))
+            .unwrap();
+        misplaced_flag(&matches)
+    }
+
+    #[test]
+    fn resolve_flags_before_a_subcommand_are_rejected() {
+        assert_eq!(misplaced(&["--format", "json", "inspect"]), Some("format"));
+        assert_eq!(misplaced(&["--table", "t", "inspect"]), Some("table"));
+        assert_eq!(misplaced(&["--blackhole", "inspect"]), Some("blackhole"));
+        assert_eq!(misplaced(&["-m", "rib", "inspect"]), None);
+        assert_eq!(misplaced(&["--format", "json", "64496"]), None);
+        assert_eq!(misplaced(&["resolve", "--format", "json", "64496"]), None);
+    }
+
+    #[test]
+    fn flags_lists_every_resolve_argument() {
+        let mut help = Vec::new();
+        resolve::ResolveOpts::clap()
+            .write_long_help(&mut help)
+            .unwrap();
+        let help = String::from_utf8(help).unwrap();
+        let mut longs: Vec<&str> = help
+            .split_whitespace()
+            .filter_map(|word| word.strip_prefix("--"))
+            .map(|name| name.trim_end_matches(|c: char| !c.is_ascii_alphanumeric()))
+            .filter(|&name| name != "help" && name != "version")
+            .chain(["asns"])
+            .collect();
+        longs.sort_unstable();
+        longs.dedup();
+        let mut flags = resolve::FLAGS.to_vec();
+        flags.sort_unstable();
+        assert_eq!(flags, longs);
+    }
+
+    #[test]
+    fn env_vars_lists_every_environment_flag() {
+        let mut help = Vec::new();
+        Opts::clap().write_long_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+        // Long lines wrap, possibly between `[env:` and the name.
+        let words: Vec<&str> = help.split_whitespace().collect();
+        let mut read: Vec<&str> = (words.windows(2))
+            .filter(|w| w[0] == "[env:")
+            .map(|w| w[1].split(['=', ']']).next().unwrap())
+            .collect();
+        read.sort_unstable();
+        let mut listed = ENV_VARS.to_vec();
+        listed.sort_unstable();
+        assert_eq!(listed, read);
     }
 }
@@ -0,0 +1,79 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::rib;
+use crate::visibility::{self, PeerKey};
+
+#[derive(StructOpt, Debug)]
+pub struct NeighborsOpts {
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+/// Side of the ASN a neighbor was seen on. Paths list the nearest AS first, so
+/// the AS before it is likely an upstream and the AS after it a downstream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Position {
+    Upstream,
+    Downstream,
+}
+
+#[derive(Default)]
+struct Observations {
+    paths: u64,
+    peers: HashSet<PeerKey>,
+}
+
+/// Lists the ASNs seen directly adjacent to each given ASN in AS paths, with
+/// the number of paths and of distinct collector peers showing the adjacency.
+/// Prepending is collapsed first so that an AS never neighbors itself.
+pub fn run(mrt_file: &Path, opts: NeighborsOpts) {
+    let mut neighbors: BTreeMap<u32, HashMap<(Position, u32), Observations>> =
+        opts.asns.iter().map(|&asn| (asn, HashMap::new())).collect();
+    rib::for_each_route(mrt_file, |route| {
+        let mut path = route.as_path.clone();
+        path.dedup();
+        for (i, asn) in path.iter().enumerate() {
+            let seen = match neighbors.get_mut(asn) {
+                Some(seen) => seen,
+                None => continue,
+            };
+            let before = i.checked_sub(1).map(|j| (Position::Upstream, path[j]));
+            let after = path.get(i + 1).map(|&n| (Position::Downstream, n));
+            for key in before.into_iter().chain(after) {
+                let observations = seen.entry(key).or_default();
+                observations.paths += 1;
+                if let Some(peer) = visibility::peer_key(route) {
+                    observations.peers.insert(peer);
+                }
+            }
+        }
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    writeln!(out, "asn\tneighbor\tposition\tpaths\tpeers").unwrap();
+    for (asn, seen) in &neighbors {
+        let mut rows: Vec<_> = seen.iter().collect();
+        rows.sort_by_key(|((position, neighbor), o)| (*position, u64::MAX - o.paths, *neighbor));
+        for ((position, neighbor), observations) in rows {
+            let position = match position {
+                Position::Upstream => "upstream",
+                Position::Downstream => "downstream",
+            };
+            writeln!(
+                out,
+                "AS{}\tAS{}\t{}\t{}\t{}",
+                asn,
+                neighbor,
+                position,
+                observations.paths,
+                observations.peers.len()
+            )
+            .unwrap();
+        }
+    }
+    out.flush().unwrap();
+}
@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::archive;
+use crate::fetch::Request;
+
+/// How long to wait for a cloud metadata service before concluding there is none.
+const METADATA_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Maps `s3://bucket/key` and `gs://bucket/key` to HTTPS requests, signed with
+/// credentials when there are any; other URIs give `None`.
+///
+/// S3 credentials are looked up like the AWS CLI does: `AWS_ACCESS_KEY_ID`,
+/// `AWS_SECRET_ACCESS_KEY` and `AWS_SESSION_TOKEN`, then the `AWS_PROFILE`
+/// (or `default`) profile of `~/.aws/credentials`, then the ECS task role and
+/// the EC2 instance profile (IMDSv2). The region comes from `AWS_REGION`,
+/// `AWS_DEFAULT_REGION` or `~/.aws/config`.
+///
+/// GCS takes a bearer token from `GOOGLE_OAUTH_ACCESS_TOKEN`, from the refresh
+/// token of `gcloud auth application-default login`, or from the metadata
+/// server of the instance. Without credentials the object must be public.
+pub fn request(uri: &str) -> Option<Request> {
+    signed_request("GET", uri, "UNSIGNED-PAYLOAD")
+}
+
+/// True for the `s3://` and `gs://` URIs handled here.
+pub fn is_object_uri(uri: &str) -> bool {
+    uri.starts_with("s3://") || uri.starts_with("gs://")
+}
+
+/// Writes `body` to the `s3://` or `gs://` object `uri`, replacing it, with
+/// credentials found as for [`request`].
+pub fn upload(uri: &str, body: &[u8]) -> Result<(), String> {
+    let payload_hash = format!("{:x}", Sha256::digest(body));
+    let request = signed_request("PUT", uri, &payload_hash)
+        .ok_or_else(|| format!("{}: expected s3://bucket/key or gs://bucket/key", uri))?;
+    let mut call = ureq::put(&request.url);
+    for (name, value) in &request.headers {
+        call = call.set(name, value);
+    }
+    call.send_bytes(body)
+        .map_err(|e| format!("{}: {}", uri, e))?;
+    Ok(())
+}
+
+/// A `method` request for `uri`; S3 signatures cover the body by `payload_hash`.
+fn signed_request(method: &str, uri: &str, payload_hash: &str) -> Option<Request> {
+    if let Some(path) = uri.strip_prefix("s3://") {
+        let (bucket, key) = path.split_once('/')?;
+        return Some(s3_request(method, bucket, key, payload_hash));
+    }
+    let path = uri.strip_prefix("gs://")?;
+    let mut headers = Vec::new();
+    if let Some(token) = gcs_token() {
+        headers.push(("Authorization".to_string(), format!("Bearer {}", token)));
+    }
+    Some(Request {
+        url: format!("https://storage.googleapis.com/{}", uri_encode(path)),
+        headers,
+    })
+}
+
+struct Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+fn s3_request(method: &str, bucket: &str, key: &str, payload_hash: &str) -> Request {
+    let profile = aws_profile();
+    let region = env::var("AWS_REGION")
+        .or_else(|_| env::var("AWS_DEFAULT_REGION"))
+        .ok()
+        .or_else(|| aws_config_region(&profile))
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let host = format!("{}.s3.{}.amazonaws.com", bucket, region);
+    let path = format!("/{}", uri_encode(key));
+    let url = format!("https://{}{}", host, path);
+    let credentials = match aws_credentials(&profile) {
+        Some(credentials) => credentials,
+        None => {
+            return Request {
+                url,
+                headers: Vec::new(),
+            }
+        }
+    };
+
+    let timestamp = amz_timestamp(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs(),
+    );
+    let mut headers = vec![
+        ("host".to_string(), host),
+        ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+        ("x-amz-date".to_string(), timestamp.clone()),
+    ];
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    let authorization = authorization(
+        &credentials,
+        &Canonical {
+            method,
+            path: &path,
+            query: "",
+            headers: &headers,
+            payload_hash,
+        },
+        &timestamp,
+        &region,
+        "s3",
+    );
+    headers.push(("Authorization".to_string(), authorization));
+    // ureq sets Host from the URL itself.
+    headers.retain(|(name, _)| name != "host");
+    Request { url, headers }
+}
+
+/// `YYYYMMDDTHHMMSSZ` of a Unix time, as used by `x-amz-date`.
+fn amz_timestamp(now: u64) -> String {
+    let (year, month, day) = archive::civil_from_days((now / 86400) as u32);
+    let seconds = now % 86400;
+    format!(
+        "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// The parts of a request covered by an AWS Signature Version 4. `path` and
+/// `query` are already URI-encoded and `query` has its parameters sorted;
+/// header names are lower case.
+struct Canonical<'a> {
+    method: &'a str,
+    path: &'a str,
+    query: &'a str,
+    headers: &'a [(String, String)],
+    payload_hash: &'a str,
+}
+
+/// The `Authorization` header value signing `request` with Signature Version 4.
+fn authorization(
+    credentials: &Credentials,
+    request: &Canonical,
+    timestamp: &str,
+    region: &str,
+    service: &str,
+) -> String {
+    let mut headers: Vec<&(String, String)> = request.headers.iter().collect();
+    headers.sort();
+    let signed_headers: Vec<&str> = headers.iter().map(|(name, _)| name.as_str()).collect();
+    let signed_headers = signed_headers.join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        request.path,
+        request.query,
+        canonical_headers,
+        signed_headers,
+        request.payload_hash
+    );
+    let date = &timestamp[..8];
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+        timestamp,
+        scope,
+        Sha256::digest(canonical_request.as_bytes())
+    );
+    let key = signing_key(&credentials.secret_key, date, region, service);
+    let signature: String = hmac_sha256(&key, string_to_sign.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key, scope, signed_headers, signature
+    )
+}
+
+fn signing_key(secret_key: &str, date: &str, region: &str, service: &str) -> [u8; 32] {
+    let mut key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes());
+    for part in [region, service, "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    key
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().into()
+}
+
+fn aws_profile() -> String {
+    env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string())
+}
+
+fn home_dir() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+}
+
+/// An AWS CLI configuration file, from `var` or else `~/.aws/<name>`.
+fn aws_file(var: &str, name: &str) -> Option<String> {
+    let path = env::var_os(var)
+        .map(PathBuf::from)
+        .or_else(|| Some(home_dir()?.join(".aws").join(name)))?;
+    fs::read_to_string(path).ok()
+}
+
+/// The `key = value` pairs of the `[section]` of an INI file.
+fn ini_section(content: &str, section: &str) -> Option<HashMap<String, String>> {
+    let mut values = None;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with(['#', ';']) || line.is_empty() {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if values.is_some() {
+                break;
+            }
+            if name.trim() == section {
+                values = Some(HashMap::new());
+            }
+            continue;
+        }
+        if let (Some(values), Some((key, value))) = (&mut values, line.split_once('=')) {
+            values.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    values
+}
+
+fn aws_config_region(profile: &str) -> Option<String> {
+    let config = aws_file("AWS_CONFIG_FILE", "config")?;
+    let section = match profile {
+        "default" => "default".to_string(),
+        profile => format!("profile {}", profile),
+    };
+    ini_section(&config, &section)?.remove("region")
+}
+
+fn aws_credentials(profile: &str) -> Option<Credentials> {
+    if let (Ok(access_key), Ok(secret_key)) = (
+        env::var("AWS_ACCESS_KEY_ID"),
+        env::var("AWS_SECRET_ACCESS_KEY"),
+    ) {
+        return Some(Credentials {
+            access_key,
+            secret_key,
+            session_token: env::var("AWS_SESSION_TOKEN").ok(),
+        });
+    }
+    let from_file = aws_file("AWS_SHARED_CREDENTIALS_FILE", "credentials")
+        .and_then(|content| ini_section(&content, profile))
+        .and_then(|mut section| {
+            Some(Credentials {
+                access_key: section.remove("aws_access_key_id")?,
+                secret_key: section.remove("aws_secret_access_key")?,
+                session_token: section.remove("aws_session_token"),
+            })
+        });
+    from_file
+        .or_else(ecs_credentials)
+        .or_else(instance_credentials)
+}
+
+/// Temporary credentials as served by the ECS and EC2 metadata endpoints.
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct MetadataCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    token: String,
+}
+
+impl From<MetadataCredentials> for Credentials {
+    fn from(c: MetadataCredentials) -> Self {
+        Credentials {
+            access_key: c.access_key_id,
+            secret_key: c.secret_access_key,
+            session_token: Some(c.token),
+        }
+    }
+}
+
+fn metadata_agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(METADATA_TIMEOUT)
+        .timeout(METADATA_TIMEOUT * 3)
+        .build()
+}
+
+/// Credentials of the ECS task role.
+fn ecs_credentials() -> Option<Credentials> {
+    let uri = env::var("AWS_CONTAINER_CREDENTIALS_RELATIVE_URI").ok()?;
+    let response = metadata_agent()
+        .get(&format!("http://169.254.170.2{}", uri))
+        .call()
+        .ok()?;
+    response
+        .into_json::<MetadataCredentials>()
+        .ok()
+        .map(Credentials::from)
+}
+
+/// Credentials of the EC2 instance profile, fetched with an IMDSv2 session token.
+fn instance_credentials() -> Option<Credentials> {
+    if env::var("AWS_EC2_METADATA_DISABLED").is_ok_and(|v| v.eq_ignore_ascii_case("true")) {
+        return None;
+    }
+    let base = "http://169.254.169.254/latest";
+    let agent = metadata_agent();
+    let token = agent
+        .put(&format!("{}/api/token", base))
+        .set("X-aws-ec2-metadata-token-ttl-seconds", "300")
+        .call()
+        .ok()?
+        .into_string()
+        .ok()?;
+    let get = |path: &str| {
+        agent
+            .get(&format!(
+                "{}/meta-data/iam/security-credentials/{}",
+                base, path
+            ))
+            .set("X-aws-ec2-metadata-token", &token)
+            .call()
+            .ok()
+    };
+    let role = get("")?.into_string().ok()?;
+    let role = role.lines().next()?.trim();
+    get(role)?
+        .into_json::<MetadataCredentials>()
+        .ok()
+        .map(Credentials::from)
+}
+
+#[derive(Deserialize)]
+struct AccessToken {
+    access_token: String,
+}
+
+/// `gcloud auth application-default login` credentials.
+#[derive(Deserialize)]
+struct AuthorizedUser {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    client_id: String,
+    #[serde(default)]
+    client_secret: String,
+    #[serde(default)]
+    refresh_token: String,
+}
+
+fn gcs_token() -> Option<String> {
+    if let Ok(token) = env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        return Some(token);
+    }
+    gcloud_user_token().or_else(gce_token)
+}
+
+/// An access token for the application default credentials of a user, from
+/// `GOOGLE_APPLICATION_CREDENTIALS` or gcloud's well-known location. Service
+/// account key files would need RS256 signing and are not supported; on GCE
+/// the attached service account is used through the metadata server instead.
+fn gcloud_user_token() -> Option<String> {
+    let path = env::var_os("GOOGLE_APPLICATION_CREDENTIALS")
+        .map(PathBuf::from)
+        .or_else(|| {
+            let config = env::var_os("CLOUDSDK_CONFIG")
+                .map(PathBuf::from)
+                .or_else(|| Some(home_dir()?.join(".config").join("gcloud")))?;
+            Some(config.join("application_default_credentials.json"))
+        })?;
+    let content = fs::read_to_string(&path).ok()?;
+    let user: AuthorizedUser = serde_json::from_str(&content).ok()?;
+    if user.kind != "authorized_user" {
+        eprintln!(
+            "warning: {}: {} credentials are not supported",
+            path.display(),
+            user.kind
+        );
+        return None;
+    }
+    let response = ureq::post("https://oauth2.googleapis.com/token")
+        .send_form(&[
+            ("client_id", &user.client_id),
+            ("client_secret", &user.client_secret),
+            ("refresh_token", &user.refresh_token),
+            ("grant_type", "refresh_token"),
+        ])
+        .map_err(|e| eprintln!("warning: {}: {}", path.display(), e))
+        .ok()?;
+    response
+        .into_json::<AccessToken>()
+        .ok()
+        .map(|t| t.access_token)
+}
+
+/// An access token of the instance's service account from the GCE metadata server.
+fn gce_token() -> Option<String> {
+    let host = env::var("GCE_METADATA_HOST").unwrap_or_else(|_| "169.254.169.254".to_string());
+    let url = format!(
+        "http://{}/computeMetadata/v1/instance/service-accounts/default/token",
+        host
+    );
+    let response = metadata_agent()
+        .get(&url)
+        .set("Metadata-Flavor", "Google")
+        .call()
+        .ok()?;
+    response
+        .into_json::<AccessToken>()
+        .ok()
+        .map(|t| t.access_token)
+}
+
+/// Percent-encodes everything but unreserved characters and `/`.
+fn uri_encode(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Credentials of the AWS Signature Version 4 test suite.
+    fn example_credentials() -> Credentials {
+        Credentials {
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        }
+    }
+
+    const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        (pairs.iter())
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn signing_key_matches_aws_example() {
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        );
+        let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(
+            hex,
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn get_vanilla() {
+        let headers = headers(&[
+            ("host", "example.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+        let request = Canonical {
+            method: "GET",
+            path: "/",
+            query: "",
+            headers: &headers,
+            payload_hash: EMPTY_SHA256,
+        };
+        assert_eq!(
+            authorization(
+                &example_credentials(),
+                &request,
+                "20150830T123600Z",
+                "us-east-1",
+                "service"
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn post_vanilla() {
+        let headers = headers(&[
+            ("x-amz-date", "20150830T123600Z"),
+            ("host", "example.amazonaws.com"),
+        ]);
+        let request = Canonical {
+            method: "POST",
+            path: "/",
+            query: "",
+            headers: &headers,
+            payload_hash: EMPTY_SHA256,
+        };
+        assert_eq!(
+            authorization(
+                &example_credentials(),
+                &request,
+                "20150830T123600Z",
+                "us-east-1",
+                "service"
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"
+        );
+    }
+
+    #[test]
+    fn iam_list_users_with_query() {
+        let headers = headers(&[
+            (
+                "content-type",
+                "application/x-www-form-urlencoded; charset=utf-8",
+            ),
+            ("host", "iam.amazonaws.com"),
+            ("x-amz-date", "20150830T123600Z"),
+        ]);
+        let request = Canonical {
+            method: "GET",
+            path: "/",
+            query: "Action=ListUsers&Version=2010-05-08",
+            headers: &headers,
+            payload_hash: EMPTY_SHA256,
+        };
+        assert_eq!(
+            authorization(
+                &example_credentials(),
+                &request,
+                "20150830T123600Z",
+                "us-east-1",
+                "iam"
+            ),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn amz_timestamp_of_unix_time() {
+        assert_eq!(amz_timestamp(1440938160), "20150830T123600Z");
+    }
+
+    #[test]
+    fn ini_sections() {
+        let content = "\
+# comment
+[default]
+aws_access_key_id = AKIDDEFAULT
+aws_secret_access_key=secret
+
+[ci]
+aws_access_key_id = AKIDCI
+";
+        let default = ini_section(content, "default").unwrap();
+        assert_eq!(default["aws_access_key_id"], "AKIDDEFAULT");
+        assert_eq!(default["aws_secret_access_key"], "secret");
+        assert_eq!(ini_section(content, "ci").unwrap().len(), 1);
+        assert!(ini_section(content, "missing").is_none());
+    }
+}
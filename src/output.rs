@@ -0,0 +1,1393 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::delegated::Delegated;
+use crate::prefix::{Prefix, TooSpecific};
+use crate::setop;
+use crate::unmatched::Unmatched;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// One prefix per line.
+    Plain,
+    /// `add`/`del` lines relative to a previously published list.
+    Delta,
+    /// A JSON object holding the prefix records and the unmatched ASNs.
+    Json,
+    /// One JSON prefix record per line.
+    Ndjson,
+    /// `prefix,origin,covered_by` rows, most-specific first, for LPM table loaders.
+    P2aTrieCsv,
+    /// `cidr,label` rows for CIDR enrichment tables of log pipelines.
+    CidrMap,
+    /// A zone file fragment with each ASN's prefixes as TXT records.
+    DnsZone,
+    /// `prefix,asn,family` rows, one per originating ASN.
+    Csv,
+    /// Like csv, tab-separated.
+    Tsv,
+    /// IPv4 and IPv6 interval sets for `nft -f`.
+    Nftables,
+    /// IPv4 and IPv6 hash:net sets for `ipset restore`.
+    Ipset,
+    /// `iptables`/`ip6tables` commands appending a rule per prefix.
+    Iptables,
+    /// A persistent table definition for OpenBSD pf.conf.
+    Pf,
+    /// Numbered `ip prefix-list`/`ipv6 prefix-list` statements for FRR and Quagga.
+    Frr,
+}
+
+impl Format {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Plain => "plain",
+            Format::Delta => "delta",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::P2aTrieCsv => "p2a-trie-csv",
+            Format::CidrMap => "cidr-map",
+            Format::DnsZone => "dns-zone",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Nftables => "nftables",
+            Format::Ipset => "ipset",
+            Format::Iptables => "iptables",
+            Format::Pf => "pf",
+            Format::Frr => "frr",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Plain | Format::Delta => "txt",
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::P2aTrieCsv | Format::CidrMap | Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::DnsZone => "zone",
+            Format::Nftables => "nft",
+            Format::Ipset => "ipset",
+            Format::Iptables => "sh",
+            Format::Pf | Format::Frr => "conf",
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Format::Plain),
+            "delta" => Ok(Format::Delta),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "p2a-trie-csv" => Ok(Format::P2aTrieCsv),
+            "cidr-map" => Ok(Format::CidrMap),
+            "dns-zone" => Ok(Format::DnsZone),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "nftables" => Ok(Format::Nftables),
+            "ipset" => Ok(Format::Ipset),
+            "iptables" => Ok(Format::Iptables),
+            "pf" => Ok(Format::Pf),
+            "frr" => Ok(Format::Frr),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+/// Textual form of IPv6 prefixes, for consumers that cannot parse `::`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum V6Style {
+    /// RFC 5952 form, e.g. `2001:db8::/32`.
+    Compressed,
+    /// All eight zero-padded groups, e.g. `2001:0db8:0000:0000:0000:0000:0000:0000/32`.
+    Expanded,
+    /// RFC 5952 form with uppercase hex digits, e.g. `2001:DB8::/32`.
+    PrefixUpper,
+}
+
+impl FromStr for V6Style {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "compressed" => Ok(V6Style::Compressed),
+            "expanded" => Ok(V6Style::Expanded),
+            "prefix-upper" => Ok(V6Style::PrefixUpper),
+            _ => Err(format!("unknown v6 style: {}", s)),
+        }
+    }
+}
+
+impl V6Style {
+    pub fn format(&self, prefix: &Prefix) -> String {
+        let v6 = match prefix.addr {
+            IpAddr::V6(v6) => v6,
+            IpAddr::V4(_) => return prefix.to_string(),
+        };
+        match self {
+            V6Style::Compressed => prefix.to_string(),
+            V6Style::Expanded => {
+                let groups: Vec<String> =
+                    v6.segments().iter().map(|g| format!("{:04x}", g)).collect();
+                format!("{}/{}", groups.join(":"), prefix.len)
+            }
+            V6Style::PrefixUpper => prefix.to_string().to_uppercase(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+}
+
+impl FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(LineEnding::Lf),
+            "crlf" => Ok(LineEnding::Crlf),
+            _ => Err(format!("unknown line ending: {}", s)),
+        }
+    }
+}
+
+/// Writer adapter turning the `\n` line ends produced by the emitters into `ending`.
+struct Terminated<'a> {
+    inner: &'a mut dyn Write,
+    ending: LineEnding,
+}
+
+impl Write for Terminated<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.ending {
+            LineEnding::Lf => self.inner.write(buf),
+            LineEnding::Crlf => {
+                for (i, line) in buf.split(|&b| b == b'\n').enumerate() {
+                    if i > 0 {
+                        self.inner.write_all(b"\r\n")?;
+                    }
+                    self.inner.write_all(line)?;
+                }
+                Ok(buf.len())
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A selected prefix and the selected ASNs seen originating it.
+#[derive(Debug)]
+pub struct Entry {
+    pub prefix: Prefix,
+    pub origins: Vec<u32>,
+    /// Added or changed by a manual override rather than taken from the table.
+    pub overridden: bool,
+}
+
+/// Everything the emitters need besides the entries themselves.
+pub struct EmitOptions {
+    pub v6_style: V6Style,
+    pub line_ending: LineEnding,
+    /// Seconds since the Unix epoch after which JSON records should be considered stale.
+    pub expires_at: u64,
+    /// Previously published list the delta format is computed against.
+    pub previous: Vec<Prefix>,
+    /// RIR allocations used to annotate JSON records; may be empty.
+    pub delegated: Delegated,
+    /// Requested ASNs that matched nothing, listed by the json format.
+    pub unmatched: Vec<Unmatched>,
+    /// Group names from the overrides file, used as cidr-map labels in place of
+    /// `AS<N>`.
+    pub names: HashMap<u32, String>,
+    /// Origin of the dns-zone format.
+    pub zone: String,
+    /// Name of the set written by the nftables and ipset formats; the IPv6 set
+    /// gets `_v6` appended.
+    pub set_name: String,
+    /// Family and name of the table holding the nftables sets, e.g. `inet filter`.
+    pub nft_table: String,
+    /// Capacity of the sets created by the ipset format.
+    pub maxelem: u32,
+    /// Chain the iptables format appends to, and the target of its rules.
+    pub chain: String,
+    pub action: String,
+    /// Name of the table defined by the pf format.
+    pub pf_table: String,
+    /// Name of the prefix lists written by the frr format.
+    pub list_name: String,
+    /// How too specific prefixes were treated, which decides the frr `le` bounds.
+    pub too_specific: TooSpecific,
+    /// Text written verbatim before and after the formatted entries.
+    pub header: String,
+    pub footer: String,
+}
+
+/// Writes `entries` in `format`. Callers pass entries sorted by prefix (IPv4
+/// before IPv6, then by address and length) so that every run over the same
+/// table produces byte-identical output; formats that need another order, such
+/// as p2a-trie-csv, sort on a total key of their own. The header and footer
+/// wrap the output of every format.
+pub fn emit(
+    out: &mut dyn Write,
+    format: Format,
+    entries: &[Entry],
+    opts: &EmitOptions,
+) -> io::Result<()> {
+    let out = &mut Terminated {
+        inner: out,
+        ending: opts.line_ending,
+    };
+    out.write_all(opts.header.as_bytes())?;
+    match format {
+        Format::Plain => emit_plain(out, entries, opts),
+        Format::Delta => emit_delta(out, entries, opts),
+        Format::Json => emit_json(out, entries, opts),
+        Format::Ndjson => emit_ndjson(out, entries, opts),
+        Format::P2aTrieCsv => emit_p2a_trie_csv(out, entries, opts),
+        Format::CidrMap => emit_cidr_map(out, entries, opts),
+        Format::DnsZone => emit_dns_zone(out, entries, opts),
+        Format::Csv => emit_rows(out, entries, opts, ','),
+        Format::Tsv => emit_rows(out, entries, opts, '\t'),
+        Format::Nftables => emit_nftables(out, entries, opts),
+        Format::Ipset => emit_ipset(out, entries, opts),
+        Format::Iptables => emit_iptables(out, entries, opts),
+        Format::Pf => emit_pf(out, entries, opts),
+        Format::Frr => emit_frr(out, entries, opts),
+    }?;
+    out.write_all(opts.footer.as_bytes())
+}
+
+/// Reads a prefix list as written by the plain format, skipping blank and `#` lines.
+pub fn read_prefix_list(path: &Path) -> Vec<Prefix> {
+    let content = fs::read_to_string(path).unwrap();
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.parse::<Prefix>().unwrap())
+        .collect()
+}
+
+fn emit_plain(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    for entry in entries {
+        writeln!(out, "{}", opts.v6_style.format(&entry.prefix))?;
+    }
+    Ok(())
+}
+
+/// Withdrawals come first so that size-limited sets never overflow mid-update.
+fn emit_delta(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let current: HashSet<&Prefix> = entries.iter().map(|e| &e.prefix).collect();
+    let before: HashSet<&Prefix> = opts.previous.iter().collect();
+    for prefix in opts.previous.iter().filter(|p| !current.contains(p)) {
+        writeln!(out, "del {}", opts.v6_style.format(prefix))?;
+    }
+    for prefix in entries
+        .iter()
+        .map(|e| &e.prefix)
+        .filter(|p| !before.contains(p))
+    {
+        writeln!(out, "add {}", opts.v6_style.format(prefix))?;
+    }
+    Ok(())
+}
+
+/// Version of the JSON record layout, bumped whenever a field changes meaning
+/// or goes away. Version 1 records had no `schema_version` field, and up to
+/// version 2 the json format wrote a bare array of records.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// The document written by the json format.
+#[derive(Serialize, Debug)]
+struct Envelope<'a> {
+    schema_version: u32,
+    records: Vec<Record>,
+    unmatched: &'a [Unmatched],
+}
+
+#[derive(Serialize, Debug)]
+struct Record {
+    schema_version: u32,
+    prefix: String,
+    origins: Vec<u32>,
+    expires_at: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rir: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allocated: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    overridden: bool,
+}
+
+fn records<'a>(entries: &'a [Entry], opts: &'a EmitOptions) -> impl Iterator<Item = Record> + 'a {
+    entries.iter().map(move |entry| {
+        let allocation = opts.delegated.lookup(&entry.prefix);
+        Record {
+            schema_version: SCHEMA_VERSION,
+            prefix: opts.v6_style.format(&entry.prefix),
+            origins: entry.origins.clone(),
+            expires_at: opts.expires_at,
+            rir: allocation.map(|a| a.rir.clone()),
+            allocated: allocation.and_then(|a| a.date_string()),
+            overridden: entry.overridden,
+        }
+    })
+}
+
+/// JSON Schema of the records written by the ndjson (one per line) and json
+/// (as the `records` of an object that also lists `unmatched` ASNs) formats.
+pub fn record_schema() -> serde_json::Value {
+    serde_json::json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "$id": format!("https://github.com/gaoyifan/bgptools/schema/record-v{}.json", SCHEMA_VERSION),
+        "title": "bgptools prefix record",
+        "type": "object",
+        "required": ["schema_version", "prefix", "origins", "expires_at"],
+        "properties": {
+            "schema_version": { "const": SCHEMA_VERSION },
+            "prefix": { "type": "string", "description": "Prefix in CIDR notation" },
+            "origins": {
+                "type": "array",
+                "items": { "type": "integer", "minimum": 0, "maximum": 4294967295u32 },
+                "description": "Selected ASNs originating the prefix, ascending"
+            },
+            "expires_at": {
+                "type": "integer",
+                "description": "Unix time after which the record should not be used"
+            },
+            "rir": { "type": "string", "description": "Registry that allocated the prefix" },
+            "allocated": { "type": "string", "format": "date", "description": "Allocation date" },
+            "overridden": {
+                "const": true,
+                "description": "Present when a manual override added or changed the record"
+            }
+        },
+        "additionalProperties": false
+    })
+}
+
+fn emit_json(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let envelope = Envelope {
+        schema_version: SCHEMA_VERSION,
+        records: records(entries, opts).collect(),
+        unmatched: &opts.unmatched,
+    };
+    serde_json::to_writer_pretty(&mut *out, &envelope)?;
+    writeln!(out)
+}
+
+fn emit_ndjson(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    for record in records(entries, opts) {
+        serde_json::to_writer(&mut *out, &record)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Rows are ordered IPv4 before IPv6, longest prefix first, so a loader inserting
+/// them in order never shadows a more specific entry. `covered_by` names the
+/// nearest less specific prefix of the same output, if any.
+fn emit_p2a_trie_csv(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let index: HashMap<Prefix, &Entry> = entries.iter().map(|e| (e.prefix, e)).collect();
+    let mut sorted: Vec<&Entry> = entries.iter().collect();
+    sorted.sort_by_key(|e| (!e.prefix.is_ipv4(), u8::MAX - e.prefix.len, e.prefix.addr));
+    writeln!(out, "prefix,origin,covered_by")?;
+    for entry in sorted {
+        let covering = (0..entry.prefix.len)
+            .rev()
+            .map(|len| entry.prefix.supernet(len))
+            .find(|p| index.contains_key(p))
+            .map(|p| opts.v6_style.format(&p))
+            .unwrap_or_default();
+        for origin in &entry.origins {
+            writeln!(
+                out,
+                "{},{},{}",
+                opts.v6_style.format(&entry.prefix),
+                origin,
+                covering
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Labels name the originating ASNs, e.g. `AS64496` or `AS64496|AS64497` for
+/// multi-origin prefixes, or the group names the overrides file gives them,
+/// which Vector, Logstash and Fluent Bit can load as a CSV lookup table keyed
+/// on the CIDR column.
+fn emit_cidr_map(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    writeln!(out, "cidr,label")?;
+    for entry in entries {
+        let label: Vec<String> = entry
+            .origins
+            .iter()
+            .map(|asn| (opts.names.get(asn).cloned()).unwrap_or_else(|| format!("AS{}", asn)))
+            .collect();
+        writeln!(
+            out,
+            "{},{}",
+            opts.v6_style.format(&entry.prefix),
+            label.join("|")
+        )?;
+    }
+    Ok(())
+}
+
+/// A multi-origin prefix gets a row per ASN; a prefix without a selected origin,
+/// as in blackhole mode, gets one row with an empty ASN.
+fn emit_rows(
+    out: &mut dyn Write,
+    entries: &[Entry],
+    opts: &EmitOptions,
+    separator: char,
+) -> io::Result<()> {
+    writeln!(out, "prefix{0}asn{0}family", separator)?;
+    for entry in entries {
+        let prefix = opts.v6_style.format(&entry.prefix);
+        let family = if entry.prefix.is_ipv4() {
+            "ipv4"
+        } else {
+            "ipv6"
+        };
+        if entry.origins.is_empty() {
+            writeln!(out, "{1}{0}{0}{2}", separator, prefix, family)?;
+        }
+        for asn in &entry.origins {
+            writeln!(out, "{1}{0}{2}{0}{3}", separator, prefix, asn, family)?;
+        }
+    }
+    Ok(())
+}
+
+/// Each ASN gets TXT records at `as<N>` listing its prefixes space-separated.
+/// A record holds a single character-string of at most 255 bytes, so longer
+/// lists continue in further records of the same name. No SOA is written: the
+/// fragment is meant to be `$INCLUDE`d in a zone maintained elsewhere.
+fn emit_dns_zone(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let mut by_asn: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        for &asn in &entry.origins {
+            by_asn
+                .entry(asn)
+                .or_default()
+                .push(opts.v6_style.format(&entry.prefix));
+        }
+    }
+    writeln!(out, "$ORIGIN {}.", opts.zone.trim_end_matches('.'))?;
+    for (asn, prefixes) in by_asn {
+        let mut chunk = String::new();
+        for prefix in prefixes {
+            if !chunk.is_empty() && chunk.len() + 1 + prefix.len() > 255 {
+                writeln!(out, "as{}\tIN\tTXT\t\"{}\"", asn, chunk)?;
+                chunk.clear();
+            }
+            if !chunk.is_empty() {
+                chunk.push(' ');
+            }
+            chunk.push_str(&prefix);
+        }
+        writeln!(out, "as{}\tIN\tTXT\t\"{}\"", asn, chunk)?;
+    }
+    Ok(())
+}
+
+/// The sets are first declared empty so that the following `flush set` also
+/// works on the first load, then filled; `nft -f` applies the file as a single
+/// transaction, so the ruleset never sees a half-loaded set. Interval sets
+/// reject overlapping elements, so the prefixes are merged beforehand.
+fn emit_nftables(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let sets = [
+        (opts.set_name.clone(), "ipv4_addr", true),
+        (format!("{}_v6", opts.set_name), "ipv6_addr", false),
+    ];
+    writeln!(out, "table {} {{", opts.nft_table)?;
+    for (name, addr_type, _) in &sets {
+        writeln!(out, "\tset {} {{", name)?;
+        writeln!(out, "\t\ttype {}", addr_type)?;
+        writeln!(out, "\t\tflags interval")?;
+        writeln!(out, "\t}}")?;
+    }
+    writeln!(out, "}}")?;
+    for (name, _, _) in &sets {
+        writeln!(out, "flush set {} {}", opts.nft_table, name)?;
+    }
+    writeln!(out, "table {} {{", opts.nft_table)?;
+    for (name, addr_type, ipv4) in &sets {
+        let ranges = setop::merge(
+            entries
+                .iter()
+                .filter(|e| e.prefix.is_ipv4() == *ipv4)
+                .map(|e| e.prefix.bounds())
+                .collect(),
+        );
+        writeln!(out, "\tset {} {{", name)?;
+        writeln!(out, "\t\ttype {}", addr_type)?;
+        writeln!(out, "\t\tflags interval")?;
+        let elements: Vec<String> = (ranges.into_iter())
+            .flat_map(|(first, last)| setop::to_prefixes(*ipv4, first, last))
+            .map(|prefix| opts.v6_style.format(&prefix))
+            .collect();
+        if !elements.is_empty() {
+            writeln!(out, "\t\telements = {{")?;
+            writeln!(out, "\t\t\t{}", elements.join(",\n\t\t\t"))?;
+            writeln!(out, "\t\t}}")?;
+        }
+        writeln!(out, "\t}}")?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Each family's set is filled under a temporary name and swapped in, so a
+/// firewall matching on it never sees a partial list while `ipset restore`
+/// runs. `create -exist` makes the script work on the first load too.
+fn emit_ipset(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    let sets = [
+        (opts.set_name.clone(), "inet", true),
+        (format!("{}_v6", opts.set_name), "inet6", false),
+    ];
+    for (name, family, ipv4) in &sets {
+        let temporary = format!("{}_tmp", name);
+        for set in [name, &temporary] {
+            writeln!(
+                out,
+                "create {} hash:net family {} maxelem {} -exist",
+                set, family, opts.maxelem
+            )?;
+        }
+        writeln!(out, "flush {}", temporary)?;
+        for entry in entries.iter().filter(|e| e.prefix.is_ipv4() == *ipv4) {
+            writeln!(
+                out,
+                "add {} {}",
+                temporary,
+                opts.v6_style.format(&entry.prefix)
+            )?;
+        }
+        writeln!(out, "swap {} {}", temporary, name)?;
+        writeln!(out, "destroy {}", temporary)?;
+    }
+    Ok(())
+}
+
+/// iptables evaluates rules one by one, so prefixes are merged first to keep
+/// the chain short. The chain itself is expected to exist; `--header-file` can
+/// carry the `-N`/`-F` commands that create or empty it.
+fn emit_iptables(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    for (command, ipv4) in [("iptables", true), ("ip6tables", false)] {
+        let ranges = setop::merge(
+            entries
+                .iter()
+                .filter(|e| e.prefix.is_ipv4() == ipv4)
+                .map(|e| e.prefix.bounds())
+                .collect(),
+        );
+        for (first, last) in ranges {
+            for prefix in setop::to_prefixes(ipv4, first, last) {
+                writeln!(
+                    out,
+                    "{} -A {} -s {} -j {}",
+                    command,
+                    opts.chain,
+                    opts.v6_style.format(&prefix),
+                    opts.action
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// pf tables hold both families, so a single block lists every prefix; lines
+/// are continued with backslashes as pf.conf requires.
+fn emit_pf(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    if entries.is_empty() {
+        return writeln!(out, "table <{}> persist", opts.pf_table);
+    }
+    writeln!(out, "table <{}> persist {{ \\", opts.pf_table)?;
+    for entry in entries {
+        writeln!(out, "\t{} \\", opts.v6_style.format(&entry.prefix))?;
+    }
+    writeln!(out, "}}")
+}
+
+/// Sequence numbers step by 5, leaving room for manual entries in between.
+/// With `--too-specific aggregate` a /24 or /48 may stand for longer
+/// announcements it replaced, so those entries accept more specifics up to
+/// host length; otherwise every entry matches exactly what was announced.
+fn emit_frr(out: &mut dyn Write, entries: &[Entry], opts: &EmitOptions) -> io::Result<()> {
+    for (command, ipv4) in [("ip", true), ("ipv6", false)] {
+        let family = entries.iter().filter(|e| e.prefix.is_ipv4() == ipv4);
+        for (i, entry) in family.enumerate() {
+            let prefix = &entry.prefix;
+            let limit = if ipv4 { 24 } else { 48 };
+            let le = if opts.too_specific == TooSpecific::Aggregate && prefix.len == limit {
+                format!(" le {}", prefix.max_len())
+            } else {
+                String::new()
+            };
+            writeln!(
+                out,
+                "{} prefix-list {} seq {} permit {}{}",
+                command,
+                opts.list_name,
+                (i + 1) * 5,
+                opts.v6_style.format(prefix),
+                le
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use proptest::sample::select;
+
+    const FORMATS: [Format; 14] = [
+        Format::Plain,
+        Format::Delta,
+        Format::Json,
+        Format::Ndjson,
+        Format::P2aTrieCsv,
+        Format::CidrMap,
+        Format::DnsZone,
+        Format::Csv,
+        Format::Tsv,
+        Format::Nftables,
+        Format::Ipset,
+        Format::Iptables,
+        Format::Pf,
+        Format::Frr,
+    ];
+
+    fn entries(prefixes: &[(&str, &[u32])]) -> Vec<Entry> {
+        (prefixes.iter())
+            .map(|(prefix, origins)| Entry {
+                prefix: prefix.parse().unwrap(),
+                origins: origins.to_vec(),
+                overridden: false,
+            })
+            .collect()
+    }
+
+    fn options() -> EmitOptions {
+        EmitOptions {
+            v6_style: V6Style::Compressed,
+            line_ending: LineEnding::Lf,
+            expires_at: 1717286400,
+            previous: vec![
+                "192.0.2.0/24".parse().unwrap(),
+                "203.0.113.0/24".parse().unwrap(),
+            ],
+            delegated: Delegated::default(),
+            unmatched: vec![Unmatched {
+                asn: 64511,
+                reason: "not seen in the table".to_string(),
+                suggestions: vec![64510],
+            }],
+            names: HashMap::new(),
+            zone: "asn.example.com".to_string(),
+            set_name: "asns".to_string(),
+            nft_table: "inet filter".to_string(),
+            maxelem: 65536,
+            chain: "INPUT".to_string(),
+            action: "DROP".to_string(),
+            pf_table: "asns".to_string(),
+            list_name: "asns".to_string(),
+            too_specific: TooSpecific::Keep,
+            header: String::new(),
+            footer: String::new(),
+        }
+    }
+
+    fn output(format: Format, mut entries: Vec<Entry>) -> Vec<u8> {
+        entries.sort_unstable_by_key(|e| e.prefix);
+        let mut out = Vec::new();
+        emit(&mut out, format, &entries, &options()).unwrap();
+        out
+    }
+
+    fn render(format: Format, entries: Vec<Entry>) -> String {
+        String::from_utf8(output(format, entries)).unwrap()
+    }
+
+    fn fixture() -> Vec<Entry> {
+        entries(&[
+            ("192.0.2.0/24", &[64496, 64497]),
+            ("192.0.2.128/25", &[64496]),
+            ("198.51.100.0/24", &[64497]),
+            ("2001:db8::/32", &[64496]),
+            ("2001:db8:1::/48", &[64496]),
+        ])
+    }
+
+    /// Expected output of the fixture per format; json is checked as a value.
+    const GOLDEN: &[(Format, &str)] = &[
+        (
+            Format::Plain,
+            "192.0.2.0/24\n192.0.2.128/25\n198.51.100.0/24\n2001:db8::/32\n2001:db8:1::/48\n",
+        ),
+        (
+            Format::Delta,
+            "del 203.0.113.0/24\nadd 192.0.2.128/25\nadd 198.51.100.0/24\n\
+             add 2001:db8::/32\nadd 2001:db8:1::/48\n",
+        ),
+        (
+            Format::Ndjson,
+            "{\"schema_version\":3,\"prefix\":\"192.0.2.0/24\",\"origins\":[64496,64497],\"expires_at\":1717286400}\n\
+             {\"schema_version\":3,\"prefix\":\"192.0.2.128/25\",\"origins\":[64496],\"expires_at\":1717286400}\n\
+             {\"schema_version\":3,\"prefix\":\"198.51.100.0/24\",\"origins\":[64497],\"expires_at\":1717286400}\n\
+             {\"schema_version\":3,\"prefix\":\"2001:db8::/32\",\"origins\":[64496],\"expires_at\":1717286400}\n\
+             {\"schema_version\":3,\"prefix\":\"2001:db8:1::/48\",\"origins\":[64496],\"expires_at\":1717286400}\n",
+        ),
+        (
+            Format::P2aTrieCsv,
+            "prefix,origin,covered_by\n\
+             192.0.2.128/25,64496,192.0.2.0/24\n\
+             192.0.2.0/24,64496,\n\
+             192.0.2.0/24,64497,\n\
+             198.51.100.0/24,64497,\n\
+             2001:db8:1::/48,64496,2001:db8::/32\n\
+             2001:db8::/32,64496,\n",
+        ),
+        (
+            Format::CidrMap,
+            "cidr,label\n\
+             192.0.2.0/24,AS64496|AS64497\n\
+             192.0.2.128/25,AS64496\n\
+             198.51.100.0/24,AS64497\n\
+             2001:db8::/32,AS64496\n\
+             2001:db8:1::/48,AS64496\n",
+        ),
+        (
+            Format::DnsZone,
+            "$ORIGIN asn.example.com.\n\
+             as64496\tIN\tTXT\t\"192.0.2.0/24 192.0.2.128/25 2001:db8::/32 2001:db8:1::/48\"\n\
+             as64497\tIN\tTXT\t\"192.0.2.0/24 198.51.100.0/24\"\n",
+        ),
+        (
+            Format::Csv,
+            "prefix,asn,family\n\
+             192.0.2.0/24,64496,ipv4\n\
+             192.0.2.0/24,64497,ipv4\n\
+             192.0.2.128/25,64496,ipv4\n\
+             198.51.100.0/24,64497,ipv4\n\
+             2001:db8::/32,64496,ipv6\n\
+             2001:db8:1::/48,64496,ipv6\n",
+        ),
+        (
+            Format::Tsv,
+            "prefix\tasn\tfamily\n\
+             192.0.2.0/24\t64496\tipv4\n\
+             192.0.2.0/24\t64497\tipv4\n\
+             192.0.2.128/25\t64496\tipv4\n\
+             198.51.100.0/24\t64497\tipv4\n\
+             2001:db8::/32\t64496\tipv6\n\
+             2001:db8:1::/48\t64496\tipv6\n",
+        ),
+        (
+            Format::Nftables,
+            "table inet filter {\n\
+             \tset asns {\n\t\ttype ipv4_addr\n\t\tflags interval\n\t}\n\
+             \tset asns_v6 {\n\t\ttype ipv6_addr\n\t\tflags interval\n\t}\n\
+             }\n\
+             flush set inet filter asns\n\
+             flush set inet filter asns_v6\n\
+             table inet filter {\n\
+             \tset asns {\n\t\ttype ipv4_addr\n\t\tflags interval\n\
+             \t\telements = {\n\t\t\t192.0.2.0/24,\n\t\t\t198.51.100.0/24\n\t\t}\n\t}\n\
+             \tset asns_v6 {\n\t\ttype ipv6_addr\n\t\tflags interval\n\
+             \t\telements = {\n\t\t\t2001:db8::/32\n\t\t}\n\t}\n\
+             }\n",
+        ),
+        (
+            Format::Ipset,
+            "create asns hash:net family inet maxelem 65536 -exist\n\
+             create asns_tmp hash:net family inet maxelem 65536 -exist\n\
+             flush asns_tmp\n\
+             add asns_tmp 192.0.2.0/24\n\
+             add asns_tmp 192.0.2.128/25\n\
+             add asns_tmp 198.51.100.0/24\n\
+             swap asns_tmp asns\n\
+             destroy asns_tmp\n\
+             create asns_v6 hash:net family inet6 maxelem 65536 -exist\n\
+             create asns_v6_tmp hash:net family inet6 maxelem 65536 -exist\n\
+             flush asns_v6_tmp\n\
+             add asns_v6_tmp 2001:db8::/32\n\
+             add asns_v6_tmp 2001:db8:1::/48\n\
+             swap asns_v6_tmp asns_v6\n\
+             destroy asns_v6_tmp\n",
+        ),
+        (
+            Format::Iptables,
+            "iptables -A INPUT -s 192.0.2.0/24 -j DROP\n\
+             iptables -A INPUT -s 198.51.100.0/24 -j DROP\n\
+             ip6tables -A INPUT -s 2001:db8::/32 -j DROP\n",
+        ),
+        (
+            Format::Pf,
+            "table <asns> persist { \\\n\
+             \t192.0.2.0/24 \\\n\
+             \t192.0.2.128/25 \\\n\
+             \t198.51.100.0/24 \\\n\
+             \t2001:db8::/32 \\\n\
+             \t2001:db8:1::/48 \\\n\
+             }\n",
+        ),
+        (
+            Format::Frr,
+            "ip prefix-list asns seq 5 permit 192.0.2.0/24\n\
+             ip prefix-list asns seq 10 permit 192.0.2.128/25\n\
+             ip prefix-list asns seq 15 permit 198.51.100.0/24\n\
+             ipv6 prefix-list asns seq 5 permit 2001:db8::/32\n\
+             ipv6 prefix-list asns seq 10 permit 2001:db8:1::/48\n",
+        ),
+    ];
+
+    #[test]
+    fn golden_outputs() {
+        for &(format, expected) in GOLDEN {
+            assert_eq!(render(format, fixture()), expected, "{}", format.name());
+        }
+        let json: serde_json::Value =
+            serde_json::from_str(&render(Format::Json, fixture())).unwrap();
+        let records: Vec<serde_json::Value> = render(Format::Ndjson, fixture())
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "records": records,
+                "unmatched": [{"asn": 64511, "reason": "not seen in the table", "suggestions": [64510]}],
+            })
+        );
+        for format in FORMATS.into_iter().filter(|&f| f != Format::Json) {
+            assert!(
+                GOLDEN.iter().any(|&(f, _)| f == format),
+                "no golden output for {}",
+                format.name()
+            );
+        }
+    }
+
+    #[test]
+    fn cidr_map_labels_use_group_names() {
+        let opts = EmitOptions {
+            names: HashMap::from([(64497, "example-net".to_string())]),
+            ..options()
+        };
+        let mut out = Vec::new();
+        emit(&mut out, Format::CidrMap, &fixture()[..3], &opts).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "cidr,label\n\
+             192.0.2.0/24,AS64496|example-net\n\
+             192.0.2.128/25,AS64496\n\
+             198.51.100.0/24,example-net\n"
+        );
+    }
+
+    /// Checks the parts of each format's grammar a loader would reject.
+    fn check_syntax(format: Format, text: &str) -> Result<(), String> {
+        let prefix = |s: &str| s.parse::<Prefix>().map(|_| ());
+        let asn = |s: &str| s.parse::<u32>().map(|_| ()).map_err(|e| e.to_string());
+        let lines: Vec<&str> = text.lines().collect();
+        let fail = |line: &str| Err(format!("{}: bad line {:?}", format.name(), line));
+        match format {
+            Format::Plain => lines.iter().try_for_each(|l| prefix(l)),
+            Format::Delta => lines.iter().try_for_each(|l| match l.split_once(' ') {
+                Some(("add" | "del", p)) => prefix(p),
+                _ => fail(l),
+            }),
+            Format::Json | Format::Ndjson => {
+                let records: Vec<serde_json::Value> = if format == Format::Json {
+                    let envelope: serde_json::Value =
+                        serde_json::from_str(text).map_err(|e| e.to_string())?;
+                    if envelope["schema_version"].as_u64() != Some(SCHEMA_VERSION.into())
+                        || !envelope["unmatched"].is_array()
+                    {
+                        return Err(format!("json: bad envelope {}", envelope));
+                    }
+                    serde_json::from_value(envelope["records"].clone())
+                        .map_err(|e| e.to_string())?
+                } else {
+                    (lines.iter())
+                        .map(|l| serde_json::from_str(l).map_err(|e| e.to_string()))
+                        .collect::<Result<_, _>>()?
+                };
+                records.iter().try_for_each(|r| {
+                    prefix(r["prefix"].as_str().ok_or("record without a prefix")?)?;
+                    let version = r["schema_version"].as_u64() == Some(SCHEMA_VERSION.into());
+                    match (version, r["origins"].as_array(), r["expires_at"].as_u64()) {
+                        (true, Some(_), Some(_)) => Ok(()),
+                        _ => Err(format!("{}: bad record {}", format.name(), r)),
+                    }
+                })
+            }
+            Format::P2aTrieCsv => {
+                if lines.first() != Some(&"prefix,origin,covered_by") {
+                    return fail(lines.first().unwrap_or(&""));
+                }
+                lines[1..]
+                    .iter()
+                    .try_for_each(|l| match l.split(',').collect::<Vec<_>>()[..] {
+                        [p, origin, covered_by] => {
+                            prefix(p)?;
+                            asn(origin)?;
+                            if covered_by.is_empty() {
+                                Ok(())
+                            } else {
+                                prefix(covered_by)
+                            }
+                        }
+                        _ => fail(l),
+                    })
+            }
+            Format::CidrMap => {
+                if lines.first() != Some(&"cidr,label") {
+                    return fail(lines.first().unwrap_or(&""));
+                }
+                lines[1..].iter().try_for_each(|l| match l.split_once(',') {
+                    Some((p, label)) => {
+                        prefix(p)?;
+                        label
+                            .split('|')
+                            .try_for_each(|a| match a.strip_prefix("AS") {
+                                Some(a) => asn(a),
+                                None => fail(l),
+                            })
+                    }
+                    None => fail(l),
+                })
+            }
+            Format::Csv | Format::Tsv => {
+                let separator = if format == Format::Csv { ',' } else { '\t' };
+                let header = format!("prefix{0}asn{0}family", separator);
+                if lines.first() != Some(&header.as_str()) {
+                    return fail(lines.first().unwrap_or(&""));
+                }
+                lines[1..]
+                    .iter()
+                    .try_for_each(|l| match l.split(separator).collect::<Vec<_>>()[..] {
+                        [p, origin, family] => {
+                            prefix(p)?;
+                            if !origin.is_empty() {
+                                asn(origin)?;
+                            }
+                            match (family, p.contains(':')) {
+                                ("ipv4", false) | ("ipv6", true) => Ok(()),
+                                _ => fail(l),
+                            }
+                        }
+                        _ => fail(l),
+                    })
+            }
+            Format::Nftables => {
+                let mut depth = 0i32;
+                for c in text.chars() {
+                    depth += match c {
+                        '{' => 1,
+                        '}' => -1,
+                        _ => 0,
+                    };
+                    if depth < 0 {
+                        return Err("unbalanced braces".to_string());
+                    }
+                }
+                if depth != 0 {
+                    return Err("unbalanced braces".to_string());
+                }
+                // Interval sets reject overlapping elements.
+                let mut elements: Vec<Prefix> = (lines.iter())
+                    .filter(|l| l.starts_with("\t\t\t"))
+                    .map(|l| l.trim().trim_end_matches(',').parse())
+                    .collect::<Result<_, _>>()?;
+                elements.sort_unstable();
+                for pair in elements.windows(2) {
+                    if pair[0].is_ipv4() == pair[1].is_ipv4()
+                        && pair[0].bounds().1 >= pair[1].bounds().0
+                    {
+                        return Err(format!("{} overlaps {}", pair[0], pair[1]));
+                    }
+                }
+                Ok(())
+            }
+            Format::Ipset => {
+                let mut created = HashSet::new();
+                lines.iter().try_for_each(|l| {
+                    let words: Vec<&str> = l.split(' ').collect();
+                    match words[..] {
+                        ["create", set, "hash:net", "family", "inet" | "inet6", "maxelem", _, "-exist"] => {
+                            created.insert(set);
+                            Ok(())
+                        }
+                        ["flush" | "destroy", set] | ["add", set, _] | ["swap", set, _]
+                            if !created.contains(set) =>
+                        {
+                            Err(format!("{} used before create", set))
+                        }
+                        ["add", _, p] => prefix(p),
+                        ["flush" | "destroy", _] | ["swap", _, _] => Ok(()),
+                        _ => fail(l),
+                    }
+                })
+            }
+            Format::Iptables => lines.iter().try_for_each(|l| {
+                let words: Vec<&str> = l.split(' ').collect();
+                match words[..] {
+                    [command @ ("iptables" | "ip6tables"), "-A", _, "-s", p, "-j", _] => {
+                        let p: Prefix = p.parse()?;
+                        if p.is_ipv4() != (command == "iptables") {
+                            return fail(l);
+                        }
+                        Ok(())
+                    }
+                    _ => fail(l),
+                }
+            }),
+            Format::Pf => {
+                if lines.len() == 1 {
+                    return match lines[0].strip_prefix("table <") {
+                        Some(rest) if rest.ends_with("> persist") => Ok(()),
+                        _ => fail(lines[0]),
+                    };
+                }
+                if !lines[0].ends_with("persist { \\") || lines[lines.len() - 1] != "}" {
+                    return Err("unterminated table".to_string());
+                }
+                lines[1..lines.len() - 1].iter().try_for_each(|l| {
+                    match l.strip_prefix('\t').and_then(|l| l.strip_suffix(" \\")) {
+                        Some(p) => prefix(p),
+                        None => fail(l),
+                    }
+                })
+            }
+            Format::Frr => {
+                let mut last_seq: HashMap<&str, u32> = HashMap::new();
+                lines.iter().try_for_each(|l| {
+                    let words: Vec<&str> = l.split(' ').collect();
+                    let (command, seq, p, le) = match words[..] {
+                        [c @ ("ip" | "ipv6"), "prefix-list", _, "seq", seq, "permit", p] => {
+                            (c, seq, p, None)
+                        }
+                        [c @ ("ip" | "ipv6"), "prefix-list", _, "seq", seq, "permit", p, "le", le] => {
+                            (c, seq, p, Some(le))
+                        }
+                        _ => return fail(l),
+                    };
+                    let seq: u32 = seq.parse().map_err(|_| format!("bad seq in {:?}", l))?;
+                    let p: Prefix = p.parse()?;
+                    if last_seq.insert(command, seq).is_some_and(|last| last >= seq)
+                        || p.is_ipv4() != (command == "ip")
+                    {
+                        return fail(l);
+                    }
+                    match le.map(str::parse::<u8>) {
+                        Some(Ok(le)) if le < p.len || le > p.max_len() => fail(l),
+                        Some(Err(_)) => fail(l),
+                        _ => Ok(()),
+                    }
+                })
+            }
+            Format::DnsZone => {
+                if lines
+                    .first()
+                    .and_then(|l| l.strip_prefix("$ORIGIN "))
+                    .is_none()
+                {
+                    return fail(lines.first().unwrap_or(&""));
+                }
+                lines[1..].iter().try_for_each(
+                    |l| match l.splitn(4, '\t').collect::<Vec<_>>()[..] {
+                        [name, "IN", "TXT", text] if name.starts_with("as") => {
+                            asn(&name[2..])?;
+                            let text = (text.strip_prefix('"'))
+                                .and_then(|t| t.strip_suffix('"'))
+                                .filter(|t| t.len() <= 255 && !t.contains('"'))
+                                .ok_or_else(|| {
+                                    format!("{}: bad TXT data {:?}", format.name(), l)
+                                })?;
+                            text.split(' ').try_for_each(prefix)
+                        }
+                        _ => fail(l),
+                    },
+                )
+            }
+        }
+    }
+
+    /// Many /24s and /64s, and a prefix covering all of them.
+    fn large_fixture() -> Vec<Entry> {
+        let v4 = (0..300u32).map(|i| format!("10.{}.{}.0/24", i >> 8, i & 0xff));
+        let v6 = (0..300u32).map(|i| format!("2001:db8:0:{:x}::/64", i));
+        (v4.chain(v6).chain(["10.0.0.0/8".to_string()]))
+            .map(|prefix| Entry {
+                prefix: prefix.parse().unwrap(),
+                origins: vec![64496],
+                overridden: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn exporters_produce_loadable_output() {
+        let mut fixtures = [
+            (fixture(), "fixture"),
+            (Vec::new(), "empty"),
+            (large_fixture(), "large"),
+        ];
+        for (entries, _) in &mut fixtures {
+            entries.sort_unstable_by_key(|e| e.prefix);
+        }
+        let aggregate = EmitOptions {
+            too_specific: TooSpecific::Aggregate,
+            ..options()
+        };
+        for (entries, name) in &fixtures {
+            for opts in [&options(), &aggregate] {
+                for format in FORMATS {
+                    let mut out = Vec::new();
+                    emit(&mut out, format, entries, opts).unwrap();
+                    let text = String::from_utf8(out).unwrap();
+                    if let Err(e) = check_syntax(format, &text) {
+                        panic!("{} of the {} fixture: {}", format.name(), name, e);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn output_does_not_depend_on_table_order() {
+        let table: &[(&str, &[u32])] = &[
+            ("2001:db8::/32", &[64496]),
+            ("192.0.2.0/24", &[64496, 64511]),
+            ("192.0.2.0/25", &[64511]),
+            ("10.0.0.0/8", &[64500]),
+            ("2001:db8::/48", &[64496]),
+            ("198.51.100.0/24", &[64497]),
+        ];
+        for format in FORMATS {
+            let expected = output(format, entries(table));
+            // Every rotation of the table, forwards and backwards.
+            for i in 0..table.len() {
+                let mut rotated = table.to_vec();
+                rotated.rotate_left(i);
+                assert_eq!(output(format, entries(&rotated)), expected, "{:?}", format);
+                rotated.reverse();
+                assert_eq!(output(format, entries(&rotated)), expected, "{:?}", format);
+            }
+        }
+    }
+
+    /// Nested, touching and identically split prefixes, and the ends of both
+    /// address families.
+    const PREFIXES: &[&str] = &[
+        "10.0.0.0/8",
+        "10.0.0.0/9",
+        "10.128.0.0/9",
+        "10.0.0.0/24",
+        "10.0.1.0/24",
+        "192.0.2.0/24",
+        "192.0.2.0/25",
+        "192.0.3.0/24",
+        "255.255.255.0/24",
+        "255.255.255.255/32",
+        "::/0",
+        "2001:db8::/32",
+        "2001:db8::/48",
+        "2001:db8:8000::/33",
+        "ffff:ffff::/32",
+    ];
+
+    /// The formats that name the origins of each prefix.
+    const ATTRIBUTING: [Format; 7] = [
+        Format::Json,
+        Format::Ndjson,
+        Format::P2aTrieCsv,
+        Format::CidrMap,
+        Format::DnsZone,
+        Format::Csv,
+        Format::Tsv,
+    ];
+
+    /// The (ASN, prefix) pairs `text` attributes, sorted.
+    fn attributions(format: Format, text: &str) -> Vec<(u32, Prefix)> {
+        let lines: Vec<&str> = text.lines().collect();
+        let mut pairs: Vec<(u32, Prefix)> = match format {
+            Format::Csv | Format::Tsv | Format::P2aTrieCsv => {
+                let separator = if format == Format::Tsv { '\t' } else { ',' };
+                (lines[1..].iter())
+                    .map(|l| {
+                        let fields: Vec<&str> = l.split(separator).collect();
+                        (fields[1].parse().unwrap(), fields[0].parse().unwrap())
+                    })
+                    .collect()
+            }
+            Format::CidrMap => (lines[1..].iter())
+                .flat_map(|l| {
+                    let (prefix, label) = l.split_once(',').unwrap();
+                    let prefix: Prefix = prefix.parse().unwrap();
+                    (label.split('|'))
+                        .map(move |asn| (asn.trim_start_matches("AS").parse().unwrap(), prefix))
+                })
+                .collect(),
+            Format::Json | Format::Ndjson => {
+                let records: Vec<serde_json::Value> = if format == Format::Json {
+                    let envelope: serde_json::Value = serde_json::from_str(text).unwrap();
+                    serde_json::from_value(envelope["records"].clone()).unwrap()
+                } else {
+                    (lines.iter())
+                        .map(|l| serde_json::from_str(l).unwrap())
+                        .collect()
+                };
+                (records.iter())
+                    .flat_map(|r| {
+                        let prefix: Prefix = r["prefix"].as_str().unwrap().parse().unwrap();
+                        (r["origins"].as_array().unwrap().iter())
+                            .map(move |asn| (asn.as_u64().unwrap() as u32, prefix))
+                    })
+                    .collect()
+            }
+            Format::DnsZone => (lines[1..].iter())
+                .flat_map(|l| {
+                    let fields: Vec<&str> = l.split('\t').collect();
+                    let asn: u32 = fields[0].trim_start_matches("as").parse().unwrap();
+                    (fields[3].trim_matches('"').split(' ')).map(move |p| (asn, p.parse().unwrap()))
+                })
+                .collect(),
+            _ => unreachable!("{} does not attribute prefixes", format.name()),
+        };
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
+    }
+
+    /// The firewall formats, which merge adjacent and overlapping
+    /// announcements.
+    const MERGING: [Format; 2] = [Format::Nftables, Format::Iptables];
+
+    /// The prefixes of a merging format.
+    fn merged_prefixes(format: Format, text: &str) -> Vec<Prefix> {
+        (text.lines())
+            .filter_map(|l| match format {
+                Format::Nftables => l.strip_prefix("\t\t\t").map(|p| p.trim_end_matches(',')),
+                Format::Iptables => l.split(' ').nth(4),
+                _ => unreachable!("{} does not merge prefixes", format.name()),
+            })
+            .map(|p| p.parse().unwrap())
+            .collect()
+    }
+
+    /// Address ranges of `prefixes` in one family, merged.
+    fn space(prefixes: impl Iterator<Item = Prefix>, ipv4: bool) -> Vec<(u128, u128)> {
+        crate::setop::merge(
+            prefixes
+                .filter(|p| p.is_ipv4() == ipv4)
+                .map(|p| p.bounds())
+                .collect(),
+        )
+    }
+
+    /// The entries selecting `routes` gives, in table order.
+    fn selected(routes: &[(&str, u32)]) -> Vec<Entry> {
+        let (mut entries, mut index) = (Vec::new(), HashMap::new());
+        for &(prefix, asn) in routes {
+            crate::resolve::add_entry(&mut entries, &mut index, prefix.parse().unwrap(), Some(asn));
+        }
+        entries
+    }
+
+    proptest! {
+        /// Routes listed in any order, including origins of one prefix
+        /// reached in any order, give byte-identical output.
+        #[test]
+        fn output_does_not_depend_on_route_order(
+            (routes, shuffled) in proptest::collection::vec((select(PREFIXES), 64496u32..64500), 0..40)
+                .prop_flat_map(|routes| (Just(routes.clone()), Just(routes).prop_shuffle()))
+        ) {
+            for format in FORMATS {
+                prop_assert_eq!(
+                    output(format, selected(&routes)),
+                    output(format, selected(&shuffled)),
+                    "{}",
+                    format.name()
+                );
+            }
+        }
+
+        /// Every emitted (asn, prefix) has an announced covering prefix, the
+        /// longest match, whose origins include the ASN, and every announced
+        /// origin is emitted.
+        #[test]
+        fn emitted_origins_are_announced(
+            routes in proptest::collection::vec((select(PREFIXES), 64496u32..64500), 0..40)
+        ) {
+            let mut announced: Vec<(u32, Prefix)> = (routes.iter())
+                .map(|&(prefix, asn)| (asn, prefix.parse().unwrap()))
+                .collect();
+            announced.sort_unstable();
+            announced.dedup();
+            for format in ATTRIBUTING {
+                let pairs = attributions(format, &render(format, selected(&routes)));
+                for &(asn, prefix) in &pairs {
+                    let longest = (announced.iter())
+                        .map(|&(_, p)| p)
+                        .filter(|p| p.len <= prefix.len && p.contains(prefix.addr))
+                        .max_by_key(|p| p.len);
+                    prop_assert!(longest.is_some(), "{}: {} is not announced", format.name(), prefix);
+                    prop_assert!(
+                        announced.contains(&(asn, longest.unwrap())),
+                        "{}: AS{} does not originate {}",
+                        format.name(),
+                        asn,
+                        prefix
+                    );
+                }
+                prop_assert_eq!(&pairs, &announced, "{}", format.name());
+            }
+        }
+
+        /// Merging touching and nested announcements neither gains nor loses
+        /// address space, and leaves no two elements overlapping.
+        #[test]
+        fn merged_formats_cover_the_announced_space(
+            routes in proptest::collection::vec((select(PREFIXES), 64496u32..64500), 0..40)
+        ) {
+            for format in MERGING {
+                let prefixes = merged_prefixes(format, &render(format, selected(&routes)));
+                for ipv4 in [true, false] {
+                    let announced = space(selected(&routes).iter().map(|e| e.prefix), ipv4);
+                    prop_assert_eq!(space(prefixes.iter().copied(), ipv4), announced);
+                    let mut bounds: Vec<(u128, u128)> = (prefixes.iter())
+                        .filter(|p| p.is_ipv4() == ipv4)
+                        .map(|p| p.bounds())
+                        .collect();
+                    bounds.sort_unstable();
+                    prop_assert!(bounds.windows(2).all(|w| w[0].1 < w[1].0), "{}", format.name());
+                }
+            }
+        }
+    }
+}
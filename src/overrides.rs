@@ -0,0 +1,158 @@
+//! Manual corrections applied to the selected prefixes right before output.
+//!
+//! ```toml
+//! [[asn]]
+//! asn = 64496
+//! include = ["192.0.2.0/24"]     # originated by AS64496 whatever the table says
+//! exclude = ["198.51.100.0/25"]  # bogus origin mapping, never attributed to AS64496
+//! name = "example-net"           # label of the AS64496 group instead of `AS64496`
+//! ```
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::output::Entry;
+use crate::prefix::Prefix;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Overrides {
+    #[serde(default)]
+    asn: Vec<AsnOverride>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AsnOverride {
+    asn: u32,
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    name: Option<String>,
+}
+
+impl Overrides {
+    pub fn load(path: &Path) -> Overrides {
+        let content = fs::read_to_string(path).unwrap();
+        let overrides: Overrides = toml::from_str(&content).expect("invalid overrides file");
+        for o in &overrides.asn {
+            if let Some(name) = &o.name {
+                // Labels sit in a CSV column and are joined with `|`.
+                if name.is_empty() || name.contains([',', '|', '"', '\n', '\r']) {
+                    panic!("invalid overrides file: AS{} name {:?}", o.asn, name);
+                }
+            }
+        }
+        overrides
+    }
+
+    /// Names given to the groups of the requested ASNs.
+    pub fn names(&self, asns: &HashSet<u32>) -> HashMap<u32, String> {
+        (self.asn.iter())
+            .filter(|o| asns.contains(&o.asn))
+            .filter_map(|o| Some((o.asn, o.name.clone()?)))
+            .collect()
+    }
+
+    /// Applies the overrides of the requested ASNs to `entries`, marking every
+    /// entry they touch. Entries left without an origin are removed.
+    pub fn apply(&self, entries: &mut Vec<Entry>, asns: &HashSet<u32>) {
+        let mut index: HashMap<Prefix, usize> = entries
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.prefix, i))
+            .collect();
+        for o in self.asn.iter().filter(|o| asns.contains(&o.asn)) {
+            for prefix in o.exclude.iter().map(|p| parse(p)) {
+                if let Some(&i) = index.get(&prefix) {
+                    let entry = &mut entries[i];
+                    entry.origins.retain(|&asn| asn != o.asn);
+                    entry.overridden = true;
+                }
+            }
+            for prefix in o.include.iter().map(|p| parse(p)) {
+                let i = *index.entry(prefix).or_insert_with(|| {
+                    entries.push(Entry {
+                        prefix,
+                        origins: Vec::new(),
+                        overridden: true,
+                    });
+                    entries.len() - 1
+                });
+                let entry = &mut entries[i];
+                if let Err(pos) = entry.origins.binary_search(&o.asn) {
+                    entry.origins.insert(pos, o.asn);
+                }
+                entry.overridden = true;
+            }
+        }
+        entries.retain(|e| !(e.overridden && e.origins.is_empty()));
+    }
+}
+
+fn parse(prefix: &str) -> Prefix {
+    prefix
+        .parse()
+        .unwrap_or_else(|e| panic!("invalid overrides file: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = r#"
+        [[asn]]
+        asn = 64496
+        include = ["192.0.2.0/24"]
+        exclude = ["198.51.100.0/25"]
+        name = "example-net"
+
+        [[asn]]
+        asn = 64497
+        name = "not-requested"
+    "#;
+
+    fn entry(prefix: &str, origins: &[u32]) -> Entry {
+        Entry {
+            prefix: prefix.parse().unwrap(),
+            origins: origins.to_vec(),
+            overridden: false,
+        }
+    }
+
+    #[test]
+    fn overrides_apply_to_requested_asns() {
+        let overrides: Overrides = toml::from_str(FILE).unwrap();
+        let asns = HashSet::from([64496, 64500]);
+        let mut entries = vec![
+            entry("198.51.100.0/25", &[64496]),
+            entry("203.0.113.0/24", &[64496, 64500]),
+        ];
+        overrides.apply(&mut entries, &asns);
+        let applied: Vec<(String, Vec<u32>, bool)> = (entries.iter())
+            .map(|e| (e.prefix.to_string(), e.origins.clone(), e.overridden))
+            .collect();
+        assert_eq!(
+            applied,
+            [
+                ("203.0.113.0/24".to_string(), vec![64496, 64500], false),
+                ("192.0.2.0/24".to_string(), vec![64496], true),
+            ]
+        );
+        assert_eq!(
+            overrides.names(&asns),
+            HashMap::from([(64496, "example-net".to_string())])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "AS64496 name")]
+    fn names_must_fit_a_csv_label() {
+        let dir = std::env::temp_dir().join(format!("bgptools-test-names-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("overrides.toml");
+        fs::write(&path, "[[asn]]\nasn = 64496\nname = \"a|b\"\n").unwrap();
+        Overrides::load(&path);
+    }
+}
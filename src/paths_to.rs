@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::prefix::Prefix;
+use crate::rib;
+
+#[derive(StructOpt, Debug)]
+pub struct PathsToOpts {
+    /// Only print the N most observed paths
+    #[structopt(long)]
+    top: Option<usize>,
+
+    /// Destination address
+    ip: IpAddr,
+}
+
+/// Lists the distinct AS paths toward the most specific prefix covering an
+/// address, most observed first, like an AS-level traceroute from every peer.
+pub fn run(mrt_file: &Path, opts: PathsToOpts) {
+    let mut best: Option<Prefix> = None;
+    let mut paths: HashMap<Vec<u32>, u64> = HashMap::new();
+    rib::for_each_route(mrt_file, |route| {
+        if !route.prefix.contains(opts.ip) {
+            return;
+        }
+        match best {
+            Some(best) if best.len > route.prefix.len => return,
+            Some(best) if best.len == route.prefix.len => {}
+            _ => {
+                best = Some(route.prefix);
+                paths.clear();
+            }
+        }
+        *paths.entry(route.as_path.clone()).or_default() += 1;
+    });
+
+    let best = match best {
+        Some(best) => best,
+        None => {
+            eprintln!("warning: no prefix in the table covers {}", opts.ip);
+            return;
+        }
+    };
+    let mut paths: Vec<(Vec<u32>, u64)> = paths.into_iter().collect();
+    paths.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    paths.truncate(opts.top.unwrap_or(paths.len()));
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    writeln!(out, "# {}", best).unwrap();
+    for (path, count) in paths {
+        let path: Vec<String> = path.iter().map(|asn| asn.to_string()).collect();
+        writeln!(out, "{}\t{}", count, path.join(" ")).unwrap();
+    }
+    out.flush().unwrap();
+}
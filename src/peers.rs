@@ -0,0 +1,76 @@
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::prefix::Prefix;
+use crate::rib::{self, Peer};
+
+#[derive(StructOpt, Debug)]
+pub struct PeersOpts {
+    /// Only list peers with this ASN
+    #[structopt(long)]
+    peer_as: Vec<u32>,
+}
+
+/// Routes and distinct prefixes learned from one peer.
+#[derive(Default)]
+pub struct Counts {
+    routes: u64,
+    v4: HashSet<Prefix>,
+    v6: HashSet<Prefix>,
+}
+
+impl Counts {
+    pub fn add(&mut self, prefix: Prefix) {
+        self.routes += 1;
+        if prefix.is_ipv4() {
+            self.v4.insert(prefix);
+        } else {
+            self.v6.insert(prefix);
+        }
+    }
+}
+
+/// Lists the collector peers that contributed routes, in peer index order, with
+/// the number of routes and distinct IPv4/IPv6 prefixes learned from each.
+pub fn run(mrt_file: &Path, opts: PeersOpts) {
+    let mut peers: BTreeMap<Peer, Counts> = BTreeMap::new();
+    rib::for_each_route(mrt_file, |route| {
+        let peer = match route.peer {
+            Some(peer) => peer,
+            None => return,
+        };
+        if !opts.peer_as.is_empty() && !opts.peer_as.contains(&peer.asn) {
+            return;
+        }
+        peers.entry(peer).or_default().add(route.prefix);
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    write_table(&mut out, &peers).unwrap();
+    out.flush().unwrap();
+}
+
+/// Writes one tab-separated row per peer, after a header row.
+pub fn write_table<W: Write>(out: &mut W, peers: &BTreeMap<Peer, Counts>) -> io::Result<()> {
+    writeln!(
+        out,
+        "index\tpeer_as\tpeer_ip\tbgp_id\troutes\tv4_prefixes\tv6_prefixes"
+    )?;
+    for (peer, counts) in peers {
+        writeln!(
+            out,
+            "{}\tAS{}\t{}\t{}\t{}\t{}\t{}",
+            peer.index,
+            peer.asn,
+            peer.ip,
+            peer.bgp_id,
+            counts.routes,
+            counts.v4.len(),
+            counts.v6.len()
+        )?;
+    }
+    Ok(())
+}
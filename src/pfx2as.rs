@@ -0,0 +1,54 @@
+use std::fs::{self, File};
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+
+use crate::prefix::Prefix;
+use crate::rib::Route;
+
+/// True if `path` holds a CAIDA routeviews-prefix2as file.
+pub fn is_pfx2as(path: &Path) -> bool {
+    let mut head = Vec::new();
+    if File::open(path)
+        .and_then(|file| file.take(256).read_to_end(&mut head))
+        .is_err()
+    {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&head);
+    let fields: Vec<&str> = head.lines().next().unwrap_or("").split('\t').collect();
+    fields.len() == 3 && fields[0].parse::<IpAddr>().is_ok() && fields[1].parse::<u8>().is_ok()
+}
+
+/// Calls `f` once per origin of every `address<TAB>length<TAB>origins` line.
+/// Multi-origin prefixes list their origins separated by `_`; an origin that is
+/// an AS set (members separated by `,`) yields a route without an origin, as
+/// AS_SET segments do in MRT input. pfx2as files carry no dump time, so 0 is
+/// returned.
+pub fn read_file<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    for (n, line) in content.lines().enumerate() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let prefix = match fields[..] {
+            [addr, len, _] => format!("{}/{}", addr, len).parse::<Prefix>(),
+            _ => Err(String::new()),
+        };
+        let prefix = match prefix {
+            Ok(prefix) => prefix,
+            Err(_) => {
+                eprintln!("warning: {}:{}: malformed line", path.display(), n + 1);
+                continue;
+            }
+        };
+        for origin in fields[2].split('_') {
+            let route = Route {
+                prefix,
+                peer: None,
+                as_path: origin.parse().into_iter().collect(),
+                communities: Vec::new(),
+            };
+            f(&route);
+        }
+    }
+    Ok(0)
+}
@@ -0,0 +1,48 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use crate::prefix::Prefix;
+use crate::rib::Route;
+
+/// Collects distinct (prefix, path) pairs whose path involves one of `asns` in an AS loop.
+#[derive(Default)]
+pub struct PoisoningReport {
+    seen: HashSet<(Prefix, Vec<u32>)>,
+    paths: Vec<(Prefix, Vec<u32>)>,
+}
+
+impl PoisoningReport {
+    pub fn add(&mut self, route: &Route, asns: &HashSet<u32>) {
+        if !asns.iter().any(|&asn| is_suspicious(&route.as_path, asn)) {
+            return;
+        }
+        let key = (route.prefix, route.as_path.clone());
+        if self.seen.insert(key.clone()) {
+            self.paths.push(key);
+        }
+    }
+
+    pub fn emit(&self, out: &mut dyn Write) -> io::Result<()> {
+        for (prefix, path) in &self.paths {
+            let path: Vec<String> = path.iter().map(u32::to_string).collect();
+            writeln!(out, "{}\t{}", prefix, path.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A path is suspicious for `asn` when, prepending aside, `asn` shows up before the
+/// origin and either reappears later or sits between two occurrences of another ASN.
+/// Plain transit never loops, while poisoning (`... O P O`) always does.
+fn is_suspicious(path: &[u32], asn: u32) -> bool {
+    let mut hops = path.to_vec();
+    hops.dedup();
+    let origin = hops.len().saturating_sub(1);
+    hops.iter()
+        .enumerate()
+        .filter(|&(i, &a)| a == asn && i != origin)
+        .any(|(i, _)| {
+            let (before, after) = (&hops[..i], &hops[i + 1..]);
+            after.contains(&asn) || before.iter().any(|a| after.contains(a))
+        })
+}
@@ -0,0 +1,157 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Prefix {
+    pub addr: IpAddr,
+    pub len: u8,
+}
+
+impl Prefix {
+    /// The prefix of length `len` covering `addr`, with the host bits cleared.
+    /// Lengths beyond the width of the address family are clamped to it.
+    pub fn new(addr: IpAddr, len: u8) -> Self {
+        let prefix = Prefix { addr, len };
+        let len = len.min(prefix.max_len());
+        Prefix::from_int(addr.is_ipv4(), Prefix { len, ..prefix }.bounds().0, len)
+    }
+
+    pub fn is_ipv4(&self) -> bool {
+        self.addr.is_ipv4()
+    }
+
+    /// Address width of the prefix's family in bits.
+    pub fn max_len(&self) -> u8 {
+        if self.is_ipv4() {
+            32
+        } else {
+            128
+        }
+    }
+
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        if addr.is_ipv4() != self.is_ipv4() {
+            return false;
+        }
+        let (first, last) = self.bounds();
+        let addr = Prefix::new(addr, self.max_len()).bounds().0;
+        first <= addr && addr <= last
+    }
+
+    /// The prefix of length `len` starting at the integer address `first`.
+    pub fn from_int(ipv4: bool, first: u128, len: u8) -> Prefix {
+        let addr = if ipv4 {
+            IpAddr::V4((first as u32).into())
+        } else {
+            IpAddr::V6(first.into())
+        };
+        Prefix { addr, len }
+    }
+
+    /// The covering prefix of length `len`, which must not exceed `self.len`.
+    pub fn supernet(&self, len: u8) -> Prefix {
+        let first = Prefix { len, ..*self }.bounds().0;
+        Prefix::from_int(self.is_ipv4(), first, len)
+    }
+
+    /// First and last address covered by the prefix, as integers.
+    pub fn bounds(&self) -> (u128, u128) {
+        let addr = match self.addr {
+            IpAddr::V4(a) => u32::from(a) as u128,
+            IpAddr::V6(a) => u128::from(a),
+        };
+        let host_bits = self.max_len().saturating_sub(self.len) as u32;
+        let mask = u128::MAX.checked_shr(128 - host_bits).unwrap_or(0);
+        (addr & !mask, addr | mask)
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+impl FromStr for Prefix {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, len) = s
+            .split_once('/')
+            .ok_or_else(|| format!("{}: missing prefix length", s))?;
+        let addr: IpAddr = addr.parse().map_err(|e| format!("{}: {}", s, e))?;
+        let len: u8 = len.parse().map_err(|e| format!("{}: {}", s, e))?;
+        let prefix = Prefix { addr, len };
+        if len > prefix.max_len() {
+            return Err(format!("{}: prefix length out of range", s));
+        }
+        Ok(Prefix::new(addr, len))
+    }
+}
+
+/// What to do with announcements longer than /24 (IPv4) or /48 (IPv6), which
+/// are filtered by most networks and rarely useful in a prefix list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TooSpecific {
+    Keep,
+    Drop,
+    /// Replace the announcement with its covering /24 or /48.
+    Aggregate,
+}
+
+impl TooSpecific {
+    pub fn apply(&self, prefix: Prefix) -> Option<Prefix> {
+        let limit = if prefix.is_ipv4() { 24 } else { 48 };
+        if prefix.len <= limit {
+            return Some(prefix);
+        }
+        match self {
+            TooSpecific::Keep => Some(prefix),
+            TooSpecific::Drop => None,
+            TooSpecific::Aggregate => Some(prefix.supernet(limit)),
+        }
+    }
+}
+
+impl FromStr for TooSpecific {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(TooSpecific::Keep),
+            "drop" => Ok(TooSpecific::Drop),
+            "aggregate" => Ok(TooSpecific::Aggregate),
+            _ => Err(format!("unknown too-specific policy: {}", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_clears_host_bits_and_clamps_the_length() {
+        let prefix = |addr: &str, len| Prefix::new(addr.parse().unwrap(), len).to_string();
+        assert_eq!(prefix("192.0.2.77", 24), "192.0.2.0/24");
+        assert_eq!(prefix("192.0.2.77", 0), "0.0.0.0/0");
+        assert_eq!(prefix("192.0.2.77", 40), "192.0.2.77/32");
+        assert_eq!(prefix("2001:db8::1", 32), "2001:db8::/32");
+        assert_eq!(prefix("2001:db8::1", 200), "2001:db8::1/128");
+        assert_eq!(
+            "192.0.2.77/24".parse::<Prefix>().unwrap().to_string(),
+            "192.0.2.0/24"
+        );
+        assert!("192.0.2.0/33".parse::<Prefix>().is_err());
+    }
+
+    #[test]
+    fn bounds_of_an_overlong_prefix_is_the_address() {
+        let prefix = Prefix {
+            addr: "192.0.2.1".parse().unwrap(),
+            len: 40,
+        };
+        assert_eq!(prefix.bounds(), (0xc0000201, 0xc0000201));
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::output::Format;
+
+#[derive(Serialize, Debug)]
+pub struct Source {
+    pub path: String,
+    /// MRT dump time, seconds since the Unix epoch.
+    pub timestamp: u32,
+    /// URL the dump was downloaded from, for remote sources.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct Manifest<'a> {
+    file: String,
+    sha256: String,
+    format: &'a str,
+    asns: &'a [u32],
+    sources: Vec<Source>,
+}
+
+/// Writes `content` as `<sha256>.<ext>` under `dir` and points `manifest.json` at it.
+///
+/// Published files are never rewritten, so they can be cached and served immutably;
+/// the manifest is replaced atomically so readers never see a half-written one.
+pub fn publish(
+    dir: &Path,
+    content: &[u8],
+    asns: &[u32],
+    format: Format,
+    sources: Vec<Source>,
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let sha256 = format!("{:x}", Sha256::digest(content));
+    let file = format!("{}.{}", sha256, format.extension());
+    let path = dir.join(&file);
+    if !path.exists() {
+        let tmp = dir.join(format!(".{}.tmp", file));
+        fs::write(&tmp, content)?;
+        fs::rename(&tmp, &path)?;
+    }
+
+    let manifest = Manifest {
+        file,
+        sha256,
+        format: format.name(),
+        asns,
+        sources,
+    };
+    let tmp = dir.join(".manifest.json.tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(&manifest)?)?;
+    fs::rename(&tmp, dir.join("manifest.json"))
+}
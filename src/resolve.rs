@@ -0,0 +1,618 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use structopt::StructOpt;
+
+use crate::archive;
+use crate::cymru;
+use crate::delegated::{self, Delegated};
+use crate::fetch;
+use crate::lengths;
+use crate::output::{self, Entry, Format, LineEnding, V6Style};
+use crate::overrides;
+use crate::poisoning::PoisoningReport;
+use crate::prefix::{Prefix, TooSpecific};
+use crate::publish;
+use crate::rib::{self, MergePolicy, Route, RouteTable, Update};
+use crate::sample;
+use crate::script;
+use crate::sink::{Compression, Sink};
+use crate::summary::{self, Summary};
+use crate::unmatched::{self, MatchTracker};
+
+#[derive(StructOpt, Debug)]
+pub struct ResolveOpts {
+    /// Output format: plain, delta, json, ndjson, p2a-trie-csv, cidr-map, dns-zone, csv, tsv, nftables, ipset, iptables, pf, frr
+    #[structopt(short, long, default_value = "plain", env = "BGPTOOLS_FORMAT")]
+    format: Format,
+
+    /// Zone origin of the dns-zone format, e.g. asn.example.com
+    #[structopt(long, required_if("format", "dns-zone"), env = "BGPTOOLS_ZONE")]
+    zone: Option<String>,
+
+    /// Set name of the nftables and ipset formats; the IPv6 set gets `_v6` appended
+    #[structopt(
+        long,
+        required_if("format", "nftables"),
+        required_if("format", "ipset"),
+        env = "BGPTOOLS_SET_NAME"
+    )]
+    set_name: Option<String>,
+
+    /// Family and name of the table holding the nftables sets
+    #[structopt(long, default_value = "inet filter", env = "BGPTOOLS_NFT_TABLE")]
+    nft_table: String,
+
+    /// Maximum number of elements of each set created by the ipset format
+    #[structopt(long, default_value = "65536", env = "BGPTOOLS_MAXELEM")]
+    maxelem: u32,
+
+    /// Write the output to this file, or upload it to an s3:// or gs:// URI, instead of stdout
+    #[structopt(short, long, parse(from_os_str), env = "BGPTOOLS_OUTPUT")]
+    output: Option<PathBuf>,
+
+    /// Chain the iptables format appends its rules to
+    #[structopt(long, required_if("format", "iptables"), env = "BGPTOOLS_CHAIN")]
+    chain: Option<String>,
+
+    /// Target of the rules written by the iptables format
+    #[structopt(long, default_value = "DROP", env = "BGPTOOLS_ACTION")]
+    action: String,
+
+    /// Table name of the pf format
+    #[structopt(long, required_if("format", "pf"), env = "BGPTOOLS_TABLE")]
+    table: Option<String>,
+
+    /// Also write the prefixes one per line to this file, for `pfctl -t <table> -T replace -f`
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_TABLE_FILE")]
+    table_file: Option<PathBuf>,
+
+    /// Name of the prefix lists written by the frr format
+    #[structopt(long, required_if("format", "frr"), env = "BGPTOOLS_LIST_NAME")]
+    list_name: Option<String>,
+
+    /// File written verbatim before the output, e.g. table declarations or comments
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_HEADER_FILE")]
+    header_file: Option<PathBuf>,
+
+    /// File written verbatim after the output
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_FOOTER_FILE")]
+    footer_file: Option<PathBuf>,
+
+    /// IPv6 notation: compressed, expanded, prefix-upper
+    #[structopt(long, default_value = "compressed", env = "BGPTOOLS_V6_STYLE")]
+    v6_style: V6Style,
+
+    /// Line terminator of the output: lf, crlf
+    #[structopt(long, default_value = "lf", env = "BGPTOOLS_LINE_ENDING")]
+    line_ending: LineEnding,
+
+    /// Compress the output: none, gz, zst
+    #[structopt(long, default_value = "none", env = "BGPTOOLS_OUTPUT_COMPRESS")]
+    output_compress: Compression,
+
+    /// Previously published prefix list, used by the delta format
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_PREVIOUS")]
+    previous: Option<PathBuf>,
+
+    /// Seconds after the RIB dump time at which JSON records expire
+    #[structopt(long, default_value = "86400", env = "BGPTOOLS_VALIDITY")]
+    validity: u64,
+
+    /// RIR delegated-extended file used to annotate JSON records with RIR and allocation date
+    #[structopt(long, parse(from_os_str))]
+    delegated: Vec<PathBuf>,
+
+    /// How prefixes of several input files combine: union, intersection, latest-wins
+    #[structopt(long, default_value = "union", env = "BGPTOOLS_MERGE_POLICY")]
+    merge_policy: MergePolicy,
+
+    /// Remove private ASNs from AS paths before selecting by origin, so that they never own a prefix
+    #[structopt(long)]
+    strip_private_from_paths: bool,
+
+    /// Prefixes longer than /24 or /48: keep, drop, aggregate (into the covering /24 or /48)
+    #[structopt(long, default_value = "keep", env = "BGPTOOLS_TOO_SPECIFIC")]
+    too_specific: TooSpecific,
+
+    /// Drop the prefixes of ASNs originating more than N prefixes of any one length
+    #[structopt(long, env = "BGPTOOLS_MAX_PER_LENGTH")]
+    max_per_length: Option<usize>,
+
+    /// Merge a curated prefix list into an ASN's prefixes, as asn:file
+    #[structopt(long, parse(try_from_str = parse_extra_prefixes))]
+    extra_prefixes: Vec<(u32, PathBuf)>,
+
+    /// TOML file of per-ASN prefixes to force-include or exclude before output,
+    /// and of names labelling the ASNs' groups
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_OVERRIDES")]
+    overrides: Option<PathBuf>,
+
+    /// Only output a sample of N prefixes per ASN, largest first, for a quick look
+    #[structopt(long)]
+    sample: Option<usize>,
+
+    /// Rhai script that can rewrite the per-ASN prefix sets before output
+    #[structopt(long, parse(from_os_str))]
+    post_script: Option<PathBuf>,
+
+    /// Check the first address of a sample of the output prefixes against an
+    /// external origin service: cymru
+    #[structopt(long)]
+    verify: Option<cymru::VerifyService>,
+
+    /// Number of prefixes checked by --verify
+    #[structopt(long, default_value = "20")]
+    verify_sample: usize,
+
+    /// Also list unannounced space allocated to the organizations holding the ASNs, per --delegated
+    #[structopt(long, requires = "delegated")]
+    include_allocated: bool,
+
+    /// Only keep prefixes allocated after this date (YYYY-MM-DD), per --delegated
+    #[structopt(long, requires = "delegated", parse(try_from_str = delegated::parse_date))]
+    allocated_after: Option<u32>,
+
+    /// Write the output under its content hash into this directory, plus a manifest.json
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_PUBLISH_DIR")]
+    publish_dir: Option<PathBuf>,
+
+    /// Also keep the output under YYYY/MM/DD/ partitions of this directory
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_ARCHIVE_DIR")]
+    archive_dir: Option<PathBuf>,
+
+    /// Remove archive partitions older than this many days
+    #[structopt(long, requires = "archive-dir", env = "BGPTOOLS_RETENTION_DAYS")]
+    retention_days: Option<u32>,
+
+    /// BGP4MP updates file, or a directory or glob of them, applied to the RIB in timestamp
+    /// order, or an http(s), s3:// or gs:// URI
+    #[structopt(long, parse(from_os_str))]
+    updates: Option<PathBuf>,
+
+    /// Stop applying --updates after this Unix time, to get the table as of then
+    #[structopt(long, requires = "updates")]
+    end_time: Option<u32>,
+
+    /// Select prefixes tagged with a blackhole community instead of by origin ASN
+    #[structopt(long)]
+    blackhole: bool,
+
+    /// Community treated as blackhole, e.g. 64496:666 (default: 65535:666)
+    #[structopt(long, parse(try_from_str = parse_community))]
+    blackhole_community: Vec<u32>,
+
+    /// Ignore routes carrying this community, e.g. no-export or 65535:65281
+    #[structopt(long, number_of_values = 1, parse(try_from_str = parse_community))]
+    exclude_community: Vec<u32>,
+
+    /// Report paths where the given ASNs take part in an AS loop (possible path poisoning)
+    #[structopt(long)]
+    poisoning: bool,
+
+    /// Write a JSON run summary (counts, phase timings, peak memory) to this file
+    #[structopt(long, parse(from_os_str), env = "BGPTOOLS_SUMMARY")]
+    summary: Option<PathBuf>,
+
+    /// Do not warn about reserved or documentation ASNs among the requested ones
+    #[structopt(long, conflicts_with = "deny-reserved-asn")]
+    allow_reserved_asn: bool,
+
+    /// Refuse to run when a requested ASN is reserved or for documentation only
+    #[structopt(long)]
+    deny_reserved_asn: bool,
+
+    /// Print the JSON Schema of json/ndjson records and exit
+    #[structopt(long)]
+    pub schema: bool,
+
+    /// ASNs to select; BGPTOOLS_ASNS takes them comma-separated
+    #[structopt(
+        required_unless_one = &["blackhole", "schema"],
+        min_values = 1,
+        use_delimiter = true,
+        env = "BGPTOOLS_ASNS"
+    )]
+    asns: Vec<String>,
+}
+
+/// Names of the arguments of [`ResolveOpts`], which are also accepted before
+/// any subcommand for compatibility.
+pub const FLAGS: &[&str] = &[
+    "format",
+    "zone",
+    "set-name",
+    "nft-table",
+    "maxelem",
+    "chain",
+    "action",
+    "table",
+    "table-file",
+    "list-name",
+    "header-file",
+    "footer-file",
+    "v6-style",
+    "line-ending",
+    "output",
+    "output-compress",
+    "previous",
+    "validity",
+    "delegated",
+    "merge-policy",
+    "updates",
+    "end-time",
+    "strip-private-from-paths",
+    "too-specific",
+    "max-per-length",
+    "extra-prefixes",
+    "overrides",
+    "sample",
+    "post-script",
+    "verify",
+    "verify-sample",
+    "include-allocated",
+    "allocated-after",
+    "publish-dir",
+    "archive-dir",
+    "retention-days",
+    "blackhole",
+    "blackhole-community",
+    "exclude-community",
+    "poisoning",
+    "summary",
+    "allow-reserved-asn",
+    "deny-reserved-asn",
+    "schema",
+    "asns",
+];
+
+/// Well-known BLACKHOLE community (RFC 7999).
+const BLACKHOLE: u32 = 65535 << 16 | 666;
+
+/// Parses `asn:value` or the name of a well-known community (RFC 1997, 3765, 7999).
+pub fn parse_community(s: &str) -> Result<u32, String> {
+    match s {
+        "no-export" => return Ok(0xFFFFFF01),
+        "no-advertise" => return Ok(0xFFFFFF02),
+        "no-export-subconfed" => return Ok(0xFFFFFF03),
+        "no-peer" => return Ok(0xFFFFFF04),
+        "blackhole" => return Ok(BLACKHOLE),
+        _ => {}
+    }
+    let (asn, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{}: community must be asn:value or a well-known name", s))?;
+    let asn: u16 = asn.parse().map_err(|e| format!("{}: {}", s, e))?;
+    let value: u16 = value.parse().map_err(|e| format!("{}: {}", s, e))?;
+    Ok((asn as u32) << 16 | value as u32)
+}
+
+fn open_sink(path: Option<&Path>, compression: Compression) -> Sink {
+    Sink::open(path, compression).unwrap_or_else(|e| {
+        eprintln!("error: {}: {}", path.unwrap_or("-".as_ref()).display(), e);
+        process::exit(1);
+    })
+}
+
+/// Completes the output, uploading it when it goes to an object store.
+fn finish_sink(sink: Sink) {
+    if let Err(e) = sink.finish() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Stands in for the dump time of inputs that carry none, such as text
+/// tables: the modification time of `path`, or else the current time.
+fn modified_time(path: &Path) -> u32 {
+    let modified = fs::metadata(path).and_then(|m| m.modified());
+    (modified.unwrap_or_else(|_| SystemTime::now()))
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32)
+}
+
+fn parse_extra_prefixes(s: &str) -> Result<(u32, PathBuf), String> {
+    let (asn, path) = s
+        .split_once(':')
+        .ok_or_else(|| format!("{}: expected asn:file", s))?;
+    let asn = asn.parse().map_err(|e| format!("{}: {}", s, e))?;
+    Ok((asn, PathBuf::from(path)))
+}
+
+/// Records `origin` for `prefix`, adding an entry for prefixes not seen yet.
+pub fn add_entry(
+    entries: &mut Vec<Entry>,
+    index: &mut HashMap<Prefix, usize>,
+    prefix: Prefix,
+    origin: Option<u32>,
+) {
+    let i = *index.entry(prefix).or_insert_with(|| {
+        entries.push(Entry {
+            prefix,
+            origins: Vec::new(),
+            overridden: false,
+        });
+        entries.len() - 1
+    });
+    if let Some(origin) = origin {
+        if let Err(pos) = entries[i].origins.binary_search(&origin) {
+            entries[i].origins.insert(pos, origin);
+        }
+    }
+}
+/// Selects the prefixes originated by the given ASNs, or tagged with a
+/// blackhole community, and writes them in the requested format.
+pub fn run(mrt_file: &Path, opts: ResolveOpts) {
+    if opts.schema {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&output::record_schema()).unwrap()
+        );
+        return;
+    }
+    let asn_list: HashSet<u32> = opts
+        .asns
+        .into_iter()
+        .map(|x| x.parse::<u32>().expect("args(ASN) must be a number!"))
+        .collect();
+    if !opts.allow_reserved_asn {
+        let mut reserved: Vec<(u32, &str)> = asn_list
+            .iter()
+            .filter_map(|&asn| unmatched::reserved_asn(asn).map(|why| (asn, why)))
+            .collect();
+        reserved.sort_unstable();
+        let level = if opts.deny_reserved_asn {
+            "error"
+        } else {
+            "warning"
+        };
+        for (asn, why) in &reserved {
+            eprintln!("{}: AS{} is not a public ASN ({})", level, asn, why);
+        }
+        if opts.deny_reserved_asn && !reserved.is_empty() {
+            process::exit(2);
+        }
+    }
+    if opts.poisoning {
+        let mut report = PoisoningReport::default();
+        rib::for_each_route(mrt_file, |route| report.add(route, &asn_list));
+        let mut out = BufWriter::new(io::stdout().lock());
+        report.emit(&mut out).unwrap();
+        out.flush().unwrap();
+        return;
+    }
+    let blackhole_communities: HashSet<u32> = if opts.blackhole_community.is_empty() {
+        HashSet::from([BLACKHOLE])
+    } else {
+        opts.blackhole_community.iter().copied().collect()
+    };
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut index: HashMap<Prefix, usize> = HashMap::new();
+    let mut tracker = MatchTracker::default();
+    let mut summary = Summary::default();
+    let read = summary.begin("read");
+    let mut updates: Vec<Update> = Vec::new();
+    if let Some(path) = &opts.updates {
+        rib::for_each_update(&fetch::resolve(path), |update| updates.push(update.clone()));
+        updates.sort_by_key(|u| u.time);
+    }
+    // Each input file is one snapshot; `seen` lists the snapshots a prefix is in.
+    let files = rib::expand(mrt_file);
+    let mut seen: HashMap<Prefix, Vec<usize>> = HashMap::new();
+    let mut dump_times: Vec<u32> = Vec::with_capacity(files.len());
+    let mut select = |snapshot: usize, route: &Route| {
+        let stripped;
+        let route = if opts.strip_private_from_paths {
+            stripped = route.without_private();
+            &stripped
+        } else {
+            route
+        };
+        let excluded = (route.communities.iter()).any(|c| opts.exclude_community.contains(c));
+        let selected = if opts.blackhole {
+            route
+                .communities
+                .iter()
+                .any(|c| blackhole_communities.contains(c))
+        } else {
+            tracker.add(route, &asn_list, excluded);
+            route.origin().is_some_and(|asn| asn_list.contains(&asn))
+        };
+        if excluded || !selected {
+            return;
+        }
+        let prefix = match opts.too_specific.apply(route.prefix) {
+            Some(prefix) => prefix,
+            None => return,
+        };
+        add_entry(&mut entries, &mut index, prefix, route.origin());
+        let snapshots = seen.entry(prefix).or_default();
+        if snapshots.last() != Some(&snapshot) {
+            snapshots.push(snapshot);
+        }
+    };
+    for (snapshot, file) in files.iter().enumerate() {
+        let mut routes: u64 = 0;
+        let dump_time = if opts.updates.is_some() {
+            // Updates name their session by peer address and ASN, which only
+            // the peer index table gives RIB entries.
+            let mut table = RouteTable::new();
+            let dump_time = rib::for_each_route(file, |route| {
+                routes += 1;
+                let peer = route.peer.unwrap_or_else(|| {
+                    eprintln!(
+                        "error: {}: --updates needs a RIB with a peer index table",
+                        file.display()
+                    );
+                    process::exit(1);
+                });
+                table.insert(((peer.ip, peer.asn), route.prefix), route.clone());
+            });
+            rib::apply_updates(&mut table, &updates, dump_time, opts.end_time);
+            table.values().for_each(|route| select(snapshot, route));
+            dump_time
+        } else {
+            rib::for_each_route(file, |route| {
+                routes += 1;
+                select(snapshot, route);
+            })
+        };
+        dump_times.push(if dump_time == 0 {
+            modified_time(file)
+        } else {
+            dump_time
+        });
+        summary.sources.push(summary::SourceStats {
+            path: file.display().to_string(),
+            routes,
+        });
+    }
+    let dump_time = dump_times.first().copied().unwrap_or(0);
+    match opts.merge_policy {
+        MergePolicy::Union => {}
+        MergePolicy::Intersection => entries.retain(|e| seen[&e.prefix].len() == files.len()),
+        MergePolicy::LatestWins => {
+            let latest = (0..files.len()).max_by_key(|&i| (dump_times[i], i));
+            entries.retain(|e| latest.is_some_and(|latest| seen[&e.prefix].contains(&latest)));
+        }
+    }
+    if opts.merge_policy != MergePolicy::Union {
+        index = (entries.iter().enumerate())
+            .map(|(i, e)| (e.prefix, i))
+            .collect();
+    }
+    for (asn, path) in &opts.extra_prefixes {
+        if !asn_list.contains(asn) {
+            eprintln!(
+                "warning: --extra-prefixes for AS{}, which is not selected",
+                asn
+            );
+            continue;
+        }
+        for prefix in output::read_prefix_list(path) {
+            add_entry(&mut entries, &mut index, prefix, Some(*asn));
+        }
+    }
+    summary.end(read);
+    let delegated = Delegated::load(&opts.delegated);
+    if let Some(after) = opts.allocated_after {
+        entries.retain(|e| {
+            delegated
+                .lookup(&e.prefix)
+                .and_then(|a| a.date)
+                .is_some_and(|date| date > after)
+        });
+    }
+    if opts.include_allocated {
+        delegated::include_allocated(&mut entries, &delegated, &asn_list);
+    }
+    if let Some(limit) = opts.max_per_length {
+        lengths::enforce_limit(&mut entries, limit);
+    }
+    let overrides = opts.overrides.as_deref().map(overrides::Overrides::load);
+    if let Some(overrides) = &overrides {
+        overrides.apply(&mut entries, &asn_list);
+    }
+    if let Some(path) = &opts.post_script {
+        script::run(path, &mut entries);
+    }
+    if let Some(n) = opts.sample {
+        sample::sample(&mut entries, n);
+    }
+    entries.sort_unstable_by_key(|e| e.prefix);
+    if opts.verify == Some(cymru::VerifyService::Cymru) {
+        cymru::check(&entries, opts.verify_sample);
+    }
+    summary.prefixes = entries.len();
+    if !opts.blackhole {
+        summary.unmatched = tracker.unmatched(&asn_list);
+        unmatched::warn(&summary.unmatched);
+    }
+    let emit = summary.begin("emit");
+    let emit_opts = output::EmitOptions {
+        v6_style: opts.v6_style,
+        line_ending: opts.line_ending,
+        expires_at: dump_time as u64 + opts.validity,
+        previous: opts
+            .previous
+            .as_deref()
+            .map(|path| {
+                let mut previous = output::read_prefix_list(path);
+                previous.sort_unstable();
+                previous
+            })
+            .unwrap_or_default(),
+        delegated,
+        unmatched: summary.unmatched.clone(),
+        names: (overrides.as_ref())
+            .map(|o| o.names(&asn_list))
+            .unwrap_or_default(),
+        zone: opts.zone.clone().unwrap_or_default(),
+        set_name: opts.set_name.clone().unwrap_or_default(),
+        nft_table: opts.nft_table.clone(),
+        maxelem: opts.maxelem,
+        chain: opts.chain.clone().unwrap_or_default(),
+        action: opts.action.clone(),
+        pf_table: opts.table.clone().unwrap_or_default(),
+        list_name: opts.list_name.clone().unwrap_or_default(),
+        too_specific: opts.too_specific,
+        header: (opts.header_file.as_deref())
+            .map(|path| fs::read_to_string(path).unwrap())
+            .unwrap_or_default(),
+        footer: (opts.footer_file.as_deref())
+            .map(|path| fs::read_to_string(path).unwrap())
+            .unwrap_or_default(),
+    };
+    // Publishing and archiving need the whole output in hand; otherwise stream it.
+    if opts.publish_dir.is_none() && opts.archive_dir.is_none() {
+        let mut sink = open_sink(opts.output.as_deref(), opts.output_compress);
+        output::emit(&mut sink, opts.format, &entries, &emit_opts).unwrap();
+        finish_sink(sink);
+    } else {
+        let mut rendered: Vec<u8> = Vec::new();
+        output::emit(&mut rendered, opts.format, &entries, &emit_opts).unwrap();
+        if let Some(dir) = &opts.archive_dir {
+            archive::archive(
+                dir,
+                dump_time,
+                opts.format.extension(),
+                &rendered,
+                opts.retention_days,
+            )
+            .unwrap();
+        }
+        if let Some(dir) = &opts.publish_dir {
+            let mut asns: Vec<u32> = asn_list.into_iter().collect();
+            asns.sort_unstable();
+            let sources = (files.iter().zip(&dump_times))
+                .map(|(file, &timestamp)| publish::Source {
+                    path: file.display().to_string(),
+                    timestamp,
+                    mirror: fetch::served_by(file),
+                })
+                .collect();
+            publish::publish(dir, &rendered, &asns, opts.format, sources).unwrap();
+        }
+        // Published output is not echoed to stdout, but --output still gets a copy.
+        if opts.publish_dir.is_none() || opts.output.is_some() {
+            let mut sink = open_sink(opts.output.as_deref(), opts.output_compress);
+            sink.write_all(&rendered).unwrap();
+            finish_sink(sink);
+        }
+    }
+    if let Some(path) = &opts.table_file {
+        let mut file = open_sink(Some(path), Compression::None);
+        for entry in &entries {
+            writeln!(file, "{}", opts.v6_style.format(&entry.prefix)).unwrap();
+        }
+        finish_sink(file);
+    }
+    summary.end(emit);
+    if let Some(path) = &opts.summary {
+        summary.write(path).unwrap();
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use try_match::match_ok;
+
+use crate::bgp4mp;
+use crate::bgpdump;
+#[cfg(unix)]
+use crate::bird;
+use crate::cisco;
+use crate::fetch;
+use crate::pfx2as;
+use crate::prefix::Prefix;
+use crate::unmatched;
+
+macro_rules! match_or_continue {
+    ($in:expr, $p:pat $(if $guard:expr)? => $out:expr) => {
+        match match_ok!($in, $p $(if $guard)? => $out) {
+            Some(out) => out,
+            None => continue,
+        }
+    };
+
+    ($in:expr, $p:pat $(if $guard:expr)?) => {
+        match match_ok!($in, $p $(if $guard)?) {
+            Some(out) => out,
+            None => continue,
+        }
+    };
+}
+
+/// A collector peer from the PEER_INDEX_TABLE of a TABLE_DUMP_V2 file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Peer {
+    /// Position in the peer index table, as referenced by RIB entries.
+    pub index: u16,
+    pub asn: u32,
+    pub ip: IpAddr,
+    pub bgp_id: Ipv4Addr,
+}
+
+/// A single RIB entry: one prefix as seen from one peer.
+#[derive(Clone, Debug)]
+pub struct Route {
+    pub prefix: Prefix,
+    /// The peer the entry was learned from, when the dump has a peer index table.
+    pub peer: Option<Peer>,
+    /// ASNs of all AS_SEQUENCE segments, nearest peer first.
+    pub as_path: Vec<u32>,
+    pub communities: Vec<u32>,
+}
+
+impl Route {
+    pub fn origin(&self) -> Option<u32> {
+        self.as_path.last().copied()
+    }
+
+    /// ASN of the peer, falling back to the first AS in the path for dumps
+    /// without a peer index table. Route server peers do not prepend their own
+    /// ASN, so the path alone can be misleading.
+    pub fn peer_asn(&self) -> Option<u32> {
+        self.peer
+            .map(|p| p.asn)
+            .or_else(|| self.as_path.first().copied())
+    }
+
+    /// The route with private ASNs removed from its path, so that a customer
+    /// numbered privately behind its upstream leaves the upstream as origin.
+    pub fn without_private(&self) -> Route {
+        Route {
+            as_path: (self.as_path.iter().copied())
+                .filter(|&asn| !unmatched::private_asn(asn))
+                .collect(),
+            ..self.clone()
+        }
+    }
+}
+
+/// How the selections of several input files, each a snapshot of the table,
+/// are combined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergePolicy {
+    /// Prefixes seen in any snapshot.
+    Union,
+    /// Prefixes seen in every snapshot.
+    Intersection,
+    /// Prefixes of the snapshot with the latest dump time only.
+    LatestWins,
+}
+
+impl FromStr for MergePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(MergePolicy::Union),
+            "intersection" => Ok(MergePolicy::Intersection),
+            "latest-wins" => Ok(MergePolicy::LatestWins),
+            _ => Err(format!("unknown merge policy: {}", s)),
+        }
+    }
+}
+
+/// A BGP UPDATE message from an updates file.
+#[derive(Clone, Debug)]
+pub struct Update {
+    pub time: u32,
+    pub peer_asn: u32,
+    pub peer_ip: IpAddr,
+    pub withdrawn: Vec<Prefix>,
+    pub announced: Vec<Prefix>,
+    /// Path and communities shared by all announced prefixes.
+    pub as_path: Vec<u32>,
+    pub communities: Vec<u32>,
+}
+
+/// Routes of a RIB snapshot by the address and ASN of the peer that announced
+/// them, which is how BGP4MP updates name their session.
+pub type RouteTable = HashMap<((IpAddr, u32), Prefix), Route>;
+
+/// Applies the `updates`, sorted by time, that were received after the dump
+/// at `dump_time` and no later than `end_time`.
+pub fn apply_updates(
+    table: &mut RouteTable,
+    updates: &[Update],
+    dump_time: u32,
+    end_time: Option<u32>,
+) {
+    let updates = (updates.iter())
+        .skip_while(|u| u.time < dump_time)
+        .take_while(|u| end_time.is_none_or(|end| u.time <= end));
+    for update in updates {
+        let peer = (update.peer_ip, update.peer_asn);
+        for &prefix in &update.withdrawn {
+            table.remove(&(peer, prefix));
+        }
+        for &prefix in &update.announced {
+            let route = Route {
+                prefix,
+                peer: None,
+                as_path: update.as_path.clone(),
+                communities: update.communities.clone(),
+            };
+            table.insert((peer, prefix), route);
+        }
+    }
+}
+
+/// The files `path` stands for: every file below it if it is a directory, the
+/// matches if it contains glob metacharacters, or else the path itself. Sorted,
+/// so that which dump comes first does not depend on directory order.
+pub fn expand(path: &Path) -> Vec<PathBuf> {
+    let pattern = path.to_string_lossy();
+    let mut files = if path.is_dir() {
+        let mut files = Vec::new();
+        walk(path, &mut files);
+        files
+    } else if pattern.contains(['*', '?', '[']) {
+        glob::glob(&pattern)
+            .unwrap_or_else(|e| panic!("{}: {}", pattern, e))
+            .filter_map(Result::ok)
+            .filter(|p| p.is_file())
+            .collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    files.sort();
+    assert!(!files.is_empty(), "{}: no MRT files found", pattern);
+    files
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            walk(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+/// Calls `f` for every IPv4/IPv6 unicast RIB entry of the TABLE_DUMP_V2 files
+/// `path` expands to and returns the dump timestamp of the first file. bzip2
+/// and gzip compressed files are unpacked to a temporary file first, and
+/// `bgpdump -m` output, `show ip bgp` text and CAIDA prefix2as files are
+/// accepted in place of binary MRT, and so is a BIRD control socket.
+pub fn for_each_route<F: FnMut(&Route)>(path: &Path, mut f: F) -> u32 {
+    let mut dump_time = None;
+    for file in expand(path) {
+        let time = with_unpacked(&file, |file| read_any(file, &mut f)).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        });
+        dump_time.get_or_insert(time);
+    }
+    dump_time.unwrap_or(0)
+}
+
+/// Runs `read` on `file`, or on an unpacked temporary copy of it when it is
+/// bzip2 or gzip compressed.
+fn with_unpacked<T>(file: &Path, read: impl FnOnce(&Path) -> T) -> T {
+    match file.extension().and_then(|e| e.to_str()) {
+        Some("bz2" | "gz") => {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let name = format!(
+                "bgptools-{}-{}.mrt",
+                process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            );
+            let unpacked = TempFile(env::temp_dir().join(name));
+            fetch::unpack(file, &unpacked.0).unwrap();
+            read(&unpacked.0)
+        }
+        _ => read(file),
+    }
+}
+
+/// A temporary file, removed when dropped, even if reading it panics.
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn read_any<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    #[cfg(unix)]
+    if bird::is_bird_socket(path) {
+        return bird::read_socket(path, f);
+    }
+    if bgpdump::is_bgpdump_text(path) {
+        bgpdump::read_file(path, f)
+    } else if cisco::is_cisco_table(path) {
+        cisco::read_file(path, f)
+    } else if pfx2as::is_pfx2as(path) {
+        pfx2as::read_file(path, f)
+    } else {
+        read_file(path, f)
+    }
+}
+
+fn read_file<F: FnMut(&Route)>(path: &Path, f: &mut F) -> Result<u32, String> {
+    let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let entries =
+        mrt::read_file_complete(file).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let dump_time = entries.first().map_or(0, |e| e.mrt_header.timestamp);
+    let mut peers: Vec<Peer> = Vec::new();
+    for entry in &entries {
+        match_or_continue!(&entry.mrt_header.mrt_type, mrt::MrtType::TABLE_DUMP_V2);
+        if let mrt::MrtMessage::PEER_INDEX_TABLE {
+            peers: peer_entries,
+            ..
+        } = &entry.message
+        {
+            peers = peer_entries
+                .iter()
+                .enumerate()
+                .map(|(i, p)| Peer {
+                    index: i as u16,
+                    asn: p.peer_as,
+                    ip: p.peer_ip_address,
+                    bgp_id: p.peer_bgp_id.into(),
+                })
+                .collect();
+            continue;
+        }
+        let (header, entries) = match_or_continue!(
+            &entry.message,
+            mrt::MrtMessage::RIB_IPV4_UNICAST { header, entries }
+                | mrt::MrtMessage::RIB_IPV6_UNICAST { header, entries } => (header, entries)
+        );
+        let prefix = Prefix::new(header.prefix, header.prefix_length);
+        for e in entries {
+            let mut route = Route {
+                prefix,
+                peer: peers.get(e.peer_index as usize).copied(),
+                as_path: Vec::new(),
+                communities: Vec::new(),
+            };
+            for a in &e.bgp_attributes {
+                match &a.value {
+                    mrt::BgpAttributeValue::AS_PATH { segments } => {
+                        for s in segments {
+                            match_or_continue!(&s.segment_type, mrt::SegmentType::AS_SEQUENCE);
+                            route.as_path.extend_from_slice(&s.asns);
+                        }
+                    }
+                    mrt::BgpAttributeValue::COMMUNITY { communities } => {
+                        route.communities.extend(
+                            (communities.iter()).map(|c| (c.asn as u32) << 16 | c.value as u32),
+                        );
+                    }
+                    _ => {}
+                }
+            }
+            f(&route);
+        }
+    }
+    Ok(dump_time)
+}
+
+/// Calls `f` for every UPDATE of the BGP4MP updates files `path` expands to,
+/// in file order.
+pub fn for_each_update<F: FnMut(&Update)>(path: &Path, mut f: F) {
+    for file in expand(path) {
+        if let Err(e) = with_unpacked(&file, |file| bgp4mp::read_file(file, &mut f)) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PEER: (IpAddr, u32) = (IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1)), 64496);
+
+    fn prefix(s: &str) -> Prefix {
+        s.parse().unwrap()
+    }
+
+    fn update(time: u32, withdrawn: &[&str], announced: &[&str], origin: u32) -> Update {
+        Update {
+            time,
+            peer_asn: PEER.1,
+            peer_ip: PEER.0,
+            withdrawn: withdrawn.iter().map(|s| prefix(s)).collect(),
+            announced: announced.iter().map(|s| prefix(s)).collect(),
+            as_path: vec![PEER.1, origin],
+            communities: Vec::new(),
+        }
+    }
+
+    fn table(routes: &[(&str, u32)]) -> RouteTable {
+        (routes.iter())
+            .map(|&(s, origin)| {
+                let route = Route {
+                    prefix: prefix(s),
+                    peer: None,
+                    as_path: vec![PEER.1, origin],
+                    communities: Vec::new(),
+                };
+                ((PEER, route.prefix), route)
+            })
+            .collect()
+    }
+
+    fn origins(table: &RouteTable) -> Vec<(String, Option<u32>)> {
+        let mut origins: Vec<_> = (table.values())
+            .map(|r| (r.prefix.to_string(), r.origin()))
+            .collect();
+        origins.sort_unstable();
+        origins
+    }
+
+    #[test]
+    fn updates_apply_in_order() {
+        let mut t = table(&[("192.0.2.0/24", 64511)]);
+        let updates = [
+            update(100, &[], &["198.51.100.0/24", "2001:db8::/32"], 64500),
+            update(200, &["192.0.2.0/24"], &[], 0),
+            update(300, &[], &["192.0.2.0/24"], 64501),
+            update(400, &["198.51.100.0/24"], &[], 0),
+        ];
+        apply_updates(&mut t, &updates, 100, None);
+        assert_eq!(
+            origins(&t),
+            [
+                ("192.0.2.0/24".to_string(), Some(64501)),
+                ("2001:db8::/32".to_string(), Some(64500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn updates_outside_the_window_are_ignored() {
+        let mut t = table(&[("192.0.2.0/24", 64511)]);
+        let updates = [
+            update(50, &["192.0.2.0/24"], &[], 0),
+            update(100, &[], &["198.51.100.0/24"], 64500),
+            update(300, &["198.51.100.0/24"], &[], 0),
+        ];
+        apply_updates(&mut t, &updates, 100, Some(200));
+        assert_eq!(
+            origins(&t),
+            [
+                ("192.0.2.0/24".to_string(), Some(64511)),
+                ("198.51.100.0/24".to_string(), Some(64500)),
+            ]
+        );
+    }
+
+    #[test]
+    fn withdrawals_only_affect_their_peer() {
+        let mut t = table(&[("192.0.2.0/24", 64511)]);
+        let other = (IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2)), 64497);
+        let route = t[&(PEER, prefix("192.0.2.0/24"))].clone();
+        t.insert((other, prefix("192.0.2.0/24")), route);
+        apply_updates(&mut t, &[update(100, &["192.0.2.0/24"], &[], 0)], 0, None);
+        assert_eq!(t.len(), 1);
+        assert!(t.contains_key(&(other, prefix("192.0.2.0/24"))));
+    }
+}
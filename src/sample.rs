@@ -0,0 +1,83 @@
+use std::collections::{BTreeMap, HashSet};
+use std::hash::Hasher;
+
+use fnv::FnvHasher;
+
+use crate::output::Entry;
+use crate::prefix::Prefix;
+
+/// Keeps at most `n` prefixes per ASN: the shortest half of them, so that the
+/// bulk of the address space shows, and a pseudo-random pick of the rest. The
+/// pick hashes the prefixes instead of drawing random numbers, so repeated runs
+/// over the same table sample the same prefixes, whatever the build. A summary
+/// line per ASN goes to stderr, keeping the output itself in its format.
+pub fn sample(entries: &mut Vec<Entry>, n: usize) {
+    let mut by_asn: BTreeMap<u32, Vec<Prefix>> = BTreeMap::new();
+    for entry in entries.iter() {
+        for &asn in &entry.origins {
+            by_asn.entry(asn).or_default().push(entry.prefix);
+        }
+    }
+    let mut kept: HashSet<Prefix> = HashSet::new();
+    for (asn, mut prefixes) in by_asn {
+        let total = prefixes.len();
+        prefixes.sort_unstable_by_key(|p| (p.len, *p));
+        let largest = n.div_ceil(2).min(total);
+        let mut rest = prefixes.split_off(largest);
+        rest.sort_unstable_by_key(|p| (scramble(p), *p));
+        rest.truncate(n - largest);
+        eprintln!(
+            "AS{}: sampled {} of {} prefixes",
+            asn,
+            largest + rest.len(),
+            total
+        );
+        kept.extend(prefixes);
+        kept.extend(rest);
+    }
+    entries.retain(|e| e.origins.is_empty() || kept.contains(&e.prefix));
+}
+
+/// FNV-1a of the first address and the length. Unlike `DefaultHasher`, whose
+/// algorithm may change between Rust releases, it is fixed, and hashing bytes
+/// spelled out here keeps it independent of how `Prefix` derives `Hash`.
+fn scramble(prefix: &Prefix) -> u64 {
+    let mut hasher = FnvHasher::default();
+    hasher.write(&prefix.bounds().0.to_be_bytes());
+    hasher.write_u8(prefix.len);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_are_stable() {
+        let mut entries: Vec<Entry> = (0..16u128)
+            .map(|i| Entry {
+                prefix: Prefix::from_int(true, 0xc0000000 | i << 8, 24),
+                origins: vec![64496],
+                overridden: false,
+            })
+            .collect();
+        entries.push(Entry {
+            prefix: "192.0.0.0/16".parse().unwrap(),
+            origins: vec![64496],
+            overridden: false,
+        });
+        sample(&mut entries, 4);
+        let kept: Vec<String> = entries.iter().map(|e| e.prefix.to_string()).collect();
+        // The /16 and the lowest /24 as the shortest half, then the two /24s
+        // with the lowest FNV-1a hashes.
+        assert_eq!(
+            kept,
+            [
+                "192.0.0.0/24",
+                "192.0.6.0/24",
+                "192.0.7.0/24",
+                "192.0.0.0/16"
+            ]
+        );
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, Scope};
+
+use crate::output::Entry;
+use crate::prefix::Prefix;
+
+/// Runs a Rhai post-processing script over the selected prefixes.
+///
+/// The script sees a `prefixes` map from ASN (as a string) to an array of
+/// prefix strings and may change it in place; whatever it holds afterwards
+/// replaces the selection. `write_file(path, text)` lets it produce extra
+/// outputs. Entries without an origin are not shown to the script and kept.
+pub fn run(path: &Path, entries: &mut Vec<Entry>) {
+    let mut by_asn: BTreeMap<u32, Array> = BTreeMap::new();
+    for entry in entries.iter() {
+        for asn in &entry.origins {
+            by_asn
+                .entry(*asn)
+                .or_default()
+                .push(Dynamic::from(entry.prefix.to_string()));
+        }
+    }
+    let prefixes: Map = by_asn
+        .into_iter()
+        .map(|(asn, prefixes)| (asn.to_string().into(), Dynamic::from_array(prefixes)))
+        .collect();
+
+    let mut engine = Engine::new();
+    engine.register_fn(
+        "write_file",
+        |path: &str, text: &str| -> Result<(), Box<EvalAltResult>> {
+            fs::write(path, text).map_err(|e| format!("{}: {}", path, e).into())
+        },
+    );
+    let mut scope = Scope::new();
+    scope.push("prefixes", prefixes);
+    if let Err(e) = engine.run_file_with_scope(&mut scope, path.to_path_buf()) {
+        eprintln!("error: {}: {}", path.display(), e);
+        process::exit(1);
+    }
+    let prefixes: Map = match scope.get_value("prefixes") {
+        Some(prefixes) => prefixes,
+        None => {
+            eprintln!("error: {}: `prefixes` is no longer a map", path.display());
+            process::exit(1);
+        }
+    };
+
+    let overridden: HashMap<Prefix, bool> =
+        entries.iter().map(|e| (e.prefix, e.overridden)).collect();
+    let mut origins: BTreeMap<Prefix, Vec<u32>> = BTreeMap::new();
+    for (asn, list) in prefixes {
+        let asn: u32 = asn
+            .parse()
+            .unwrap_or_else(|e| panic!("{}: key {}: {}", path.display(), asn, e));
+        let list = list.try_cast::<Array>().unwrap_or_else(|| {
+            panic!(
+                "{}: AS{}: expected an array of prefixes",
+                path.display(),
+                asn
+            )
+        });
+        for prefix in list {
+            let prefix: Prefix = prefix
+                .into_string()
+                .map_err(|t| format!("expected a string, got {}", t))
+                .and_then(|p| p.parse())
+                .unwrap_or_else(|e| panic!("{}: AS{}: {}", path.display(), asn, e));
+            origins.entry(prefix).or_default().push(asn);
+        }
+    }
+    entries.retain(|e| e.origins.is_empty());
+    for (prefix, mut asns) in origins {
+        asns.sort_unstable();
+        asns.dedup();
+        entries.push(Entry {
+            prefix,
+            origins: asns,
+            overridden: overridden.get(&prefix).copied().unwrap_or(false),
+        });
+    }
+}
@@ -0,0 +1,346 @@
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+use crate::output;
+use crate::prefix::Prefix;
+
+#[derive(StructOpt, Debug)]
+pub struct SetopOpts {
+    /// union, intersect or subtract (addresses in A but not in B)
+    op: SetOp,
+
+    #[structopt(parse(from_os_str))]
+    a: PathBuf,
+
+    #[structopt(parse(from_os_str))]
+    b: PathBuf,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SetOp {
+    Union,
+    Intersect,
+    Subtract,
+}
+
+impl FromStr for SetOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "union" => Ok(SetOp::Union),
+            "intersect" => Ok(SetOp::Intersect),
+            "subtract" => Ok(SetOp::Subtract),
+            _ => Err(format!("unknown set operation: {}", s)),
+        }
+    }
+}
+
+/// Sorted, disjoint, non-adjacent inclusive `[first, last]` ranges.
+type Ranges = Vec<(u128, u128)>;
+
+/// Combines two plain prefix lists address by address and prints the result
+/// as the fewest prefixes covering it, IPv4 first.
+pub fn run(opts: SetopOpts) {
+    let a = output::read_prefix_list(&opts.a);
+    let b = output::read_prefix_list(&opts.b);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for ipv4 in [true, false] {
+        let a = ranges(&a, ipv4);
+        let b = ranges(&b, ipv4);
+        let result = match opts.op {
+            SetOp::Union => merge(a.into_iter().chain(b).collect()),
+            SetOp::Intersect => intersect(&a, &b),
+            SetOp::Subtract => subtract(&a, &b),
+        };
+        for (first, last) in result {
+            for prefix in to_prefixes(ipv4, first, last) {
+                writeln!(out, "{}", prefix).unwrap();
+            }
+        }
+    }
+    out.flush().unwrap();
+}
+
+fn ranges(prefixes: &[Prefix], ipv4: bool) -> Ranges {
+    merge(
+        prefixes
+            .iter()
+            .filter(|p| p.is_ipv4() == ipv4)
+            .map(Prefix::bounds)
+            .collect(),
+    )
+}
+
+pub fn merge(mut ranges: Ranges) -> Ranges {
+    ranges.sort_unstable();
+    let mut merged: Ranges = Vec::with_capacity(ranges.len());
+    for (first, last) in ranges {
+        match merged.last_mut() {
+            Some((_, end)) if first <= end.saturating_add(1) => *end = (*end).max(last),
+            _ => merged.push((first, last)),
+        }
+    }
+    merged
+}
+
+fn intersect(a: &Ranges, b: &Ranges) -> Ranges {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let first = a[i].0.max(b[j].0);
+        let last = a[i].1.min(b[j].1);
+        if first <= last {
+            result.push((first, last));
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
+pub fn subtract(a: &Ranges, b: &Ranges) -> Ranges {
+    let mut result = Vec::new();
+    let mut j = 0;
+    for &(mut first, last) in a {
+        while j < b.len() && b[j].1 < first {
+            j += 1;
+        }
+        let mut k = j;
+        let mut covered = false;
+        while k < b.len() && b[k].0 <= last {
+            if b[k].0 > first {
+                result.push((first, b[k].0 - 1));
+            }
+            if b[k].1 >= last {
+                covered = true;
+                break;
+            }
+            first = b[k].1 + 1;
+            k += 1;
+        }
+        if !covered {
+            result.push((first, last));
+        }
+    }
+    result
+}
+
+/// Splits `[first, last]` into the fewest aligned prefixes.
+pub fn to_prefixes(ipv4: bool, mut first: u128, last: u128) -> Vec<Prefix> {
+    let width: u32 = if ipv4 { 32 } else { 128 };
+    let mut prefixes = Vec::new();
+    loop {
+        let remaining = last - first;
+        // Largest block both aligned at `first` and no bigger than what is left.
+        let fits = match remaining.checked_add(1) {
+            Some(count) => 127 - count.leading_zeros(),
+            None => 128,
+        };
+        let host_bits = first.trailing_zeros().min(fits).min(width);
+        prefixes.push(Prefix::from_int(ipv4, first, (width - host_bits) as u8));
+        let block_last = first + u128::MAX.checked_shr(128 - host_bits).unwrap_or(0);
+        if block_last >= last {
+            break;
+        }
+        first = block_last + 1;
+    }
+    prefixes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Applies `op` to two space-separated prefix lists of both families.
+    fn apply(op: SetOp, a: &str, b: &str) -> String {
+        let parse =
+            |s: &str| -> Vec<Prefix> { s.split_whitespace().map(|p| p.parse().unwrap()).collect() };
+        let (a, b) = (parse(a), parse(b));
+        let mut result = Vec::new();
+        for ipv4 in [true, false] {
+            let (a, b) = (ranges(&a, ipv4), ranges(&b, ipv4));
+            let ranges = match op {
+                SetOp::Union => merge(a.into_iter().chain(b).collect()),
+                SetOp::Intersect => intersect(&a, &b),
+                SetOp::Subtract => subtract(&a, &b),
+            };
+            for (first, last) in ranges {
+                result.extend(to_prefixes(ipv4, first, last).iter().map(Prefix::to_string));
+            }
+        }
+        result.join(" ")
+    }
+
+    #[test]
+    fn union() {
+        let cases = [
+            // Adjacent ranges merge into their common supernet.
+            ("192.0.2.0/25", "192.0.2.128/25", "192.0.2.0/24"),
+            ("10.0.0.0/24", "10.0.1.0/24 10.0.2.0/23", "10.0.0.0/22"),
+            // Adjacent but not aligned: two blocks remain.
+            ("10.0.1.0/24", "10.0.2.0/24", "10.0.1.0/24 10.0.2.0/24"),
+            // Overlapping and nested ranges.
+            ("10.0.0.0/8", "10.1.0.0/16", "10.0.0.0/8"),
+            (
+                "10.0.0.0/9 10.64.0.0/10",
+                "10.96.0.0/11 10.128.0.0/9",
+                "10.0.0.0/8",
+            ),
+            ("0.0.0.0/0", "192.0.2.0/24", "0.0.0.0/0"),
+            ("0.0.0.0/1", "128.0.0.0/1", "0.0.0.0/0"),
+            ("::/0", "2001:db8::/32", "::/0"),
+            ("::/1", "8000::/1", "::/0"),
+            (
+                "255.255.255.254/32",
+                "255.255.255.255/32",
+                "255.255.255.254/31",
+            ),
+            (
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/128",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/127",
+            ),
+            // Families never mix.
+            ("0.0.0.0/0", "::/0", "0.0.0.0/0 ::/0"),
+            ("", "", ""),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(apply(SetOp::Union, a, b), expected, "{} | {}", a, b);
+            assert_eq!(apply(SetOp::Union, b, a), expected, "{} | {}", b, a);
+        }
+    }
+
+    #[test]
+    fn intersection() {
+        let cases = [
+            ("192.0.2.0/25", "192.0.2.128/25", ""),
+            ("10.0.0.0/8", "10.1.0.0/16", "10.1.0.0/16"),
+            ("10.0.0.0/23", "10.0.1.0/24 10.0.2.0/24", "10.0.1.0/24"),
+            (
+                "10.0.0.0/24 10.0.2.0/24",
+                "10.0.0.128/25 10.0.1.0/24 10.0.2.0/25",
+                "10.0.0.128/25 10.0.2.0/25",
+            ),
+            ("0.0.0.0/0", "192.0.2.0/24 2001:db8::/32", "192.0.2.0/24"),
+            ("0.0.0.0/0", "0.0.0.0/0", "0.0.0.0/0"),
+            ("::/0", "2001:db8::/32 192.0.2.0/24", "2001:db8::/32"),
+            ("::/0", "::/0", "::/0"),
+            ("0.0.0.0/0", "::/0", ""),
+            (
+                "255.255.255.0/24",
+                "255.255.255.255/32",
+                "255.255.255.255/32",
+            ),
+            (
+                "ffff::/16",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+            ),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(apply(SetOp::Intersect, a, b), expected, "{} & {}", a, b);
+            assert_eq!(apply(SetOp::Intersect, b, a), expected, "{} & {}", b, a);
+        }
+    }
+
+    #[test]
+    fn subtraction() {
+        let cases = [
+            ("192.0.2.0/24", "192.0.2.128/25", "192.0.2.0/25"),
+            ("192.0.2.0/24", "192.0.2.0/25", "192.0.2.128/25"),
+            (
+                "192.0.2.0/24",
+                "192.0.2.64/26",
+                "192.0.2.0/26 192.0.2.128/25",
+            ),
+            // Adjacent, not overlapping: nothing is removed.
+            ("192.0.2.0/25", "192.0.2.128/25", "192.0.2.0/25"),
+            ("192.0.2.0/24", "192.0.1.0/24 192.0.3.0/24", "192.0.2.0/24"),
+            // Several holes in one range, and one hole across several ranges.
+            (
+                "10.0.0.0/22",
+                "10.0.0.0/24 10.0.2.0/24",
+                "10.0.1.0/24 10.0.3.0/24",
+            ),
+            (
+                "10.0.0.0/24 10.0.2.0/24",
+                "10.0.0.128/25 10.0.1.0/24 10.0.2.0/25",
+                "10.0.0.0/25 10.0.2.128/25",
+            ),
+            ("192.0.2.0/24", "0.0.0.0/0", ""),
+            ("0.0.0.0/0", "0.0.0.0/1", "128.0.0.0/1"),
+            ("0.0.0.0/0", "::/0", "0.0.0.0/0"),
+            ("::/0", "8000::/1", "::/1"),
+            ("::/0", "::/1", "8000::/1"),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(apply(SetOp::Subtract, a, b), expected, "{} - {}", a, b);
+        }
+    }
+
+    #[test]
+    fn top_of_the_address_space() {
+        let v4 = apply(
+            SetOp::Subtract,
+            "0.0.0.0/0",
+            "0.0.0.0/32 255.255.255.255/32",
+        );
+        let v4: Vec<&str> = v4.split(' ').collect();
+        assert_eq!(v4.len(), 62);
+        assert_eq!(v4[..2], ["0.0.0.1/32", "0.0.0.2/31"]);
+        assert_eq!(v4[60..], ["255.255.255.252/31", "255.255.255.254/32"]);
+        let v6 = apply(
+            SetOp::Subtract,
+            "ffff::/16",
+            "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+        );
+        let v6: Vec<&str> = v6.split(' ').collect();
+        assert_eq!(v6.len(), 112);
+        assert_eq!(v6[0], "ffff::/17");
+        assert_eq!(v6[111], "ffff:ffff:ffff:ffff:ffff:ffff:ffff:fffe/128");
+    }
+
+    #[test]
+    fn fewest_aligned_prefixes() {
+        let v4 = |s: &str| u32::from(s.parse::<std::net::Ipv4Addr>().unwrap()) as u128;
+        let cases = [
+            (true, 0, u32::MAX as u128, "0.0.0.0/0"),
+            (false, 0, u128::MAX, "::/0"),
+            (
+                true,
+                u32::MAX as u128,
+                u32::MAX as u128,
+                "255.255.255.255/32",
+            ),
+            (
+                false,
+                u128::MAX,
+                u128::MAX,
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff/128",
+            ),
+            (false, 1 << 127, u128::MAX, "8000::/1"),
+            (true, v4("128.0.0.0"), u32::MAX as u128, "128.0.0.0/1"),
+            (true, v4("192.0.2.0"), v4("192.0.3.255"), "192.0.2.0/23"),
+            (
+                true,
+                v4("192.0.1.0"),
+                v4("192.0.2.255"),
+                "192.0.1.0/24 192.0.2.0/24",
+            ),
+            (true, 1, 6, "0.0.0.1/32 0.0.0.2/31 0.0.0.4/31 0.0.0.6/32"),
+        ];
+        for (ipv4, first, last, expected) in cases {
+            let prefixes: Vec<String> = (to_prefixes(ipv4, first, last).iter())
+                .map(Prefix::to_string)
+                .collect();
+            assert_eq!(prefixes.join(" "), expected, "{}..{}", first, last);
+        }
+    }
+}
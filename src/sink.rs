@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use flate2::write::GzEncoder;
+
+#[cfg(feature = "object-store")]
+use crate::object_store;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Compression::None),
+            "gz" | "gzip" => Ok(Compression::Gzip),
+            "zst" | "zstd" => Ok(Compression::Zstd),
+            _ => Err(format!("unknown compression: {}", s)),
+        }
+    }
+}
+
+enum Target {
+    Stdout(StdoutLock<'static>),
+    File(File),
+    /// An `s3://` or `gs://` URI, uploaded in one piece once the sink is finished.
+    #[cfg(feature = "object-store")]
+    Object(String, Vec<u8>),
+}
+
+impl Write for Target {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Target::Stdout(out) => out.write(buf),
+            Target::File(file) => file.write(buf),
+            #[cfg(feature = "object-store")]
+            Target::Object(_, body) => body.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Target::Stdout(out) => out.flush(),
+            Target::File(file) => file.flush(),
+            #[cfg(feature = "object-store")]
+            Target::Object(..) => Ok(()),
+        }
+    }
+}
+
+/// Buffered, optionally compressed destination for the main output.
+///
+/// Callers must `finish` the sink: compressed streams are only complete once
+/// their trailer is written, and errors there should not be swallowed by a drop.
+pub struct Sink(Encoder);
+
+enum Encoder {
+    Plain(BufWriter<Target>),
+    Gzip(GzEncoder<BufWriter<Target>>),
+    Zstd(zstd::Encoder<'static, BufWriter<Target>>),
+}
+
+impl Sink {
+    /// Opens `path`, or stdout when no path is given. With the object-store
+    /// feature, an `s3://` or `gs://` path is collected in memory and uploaded
+    /// by `finish`.
+    pub fn open(path: Option<&Path>, compression: Compression) -> io::Result<Sink> {
+        let target = match path {
+            #[cfg(feature = "object-store")]
+            Some(path) if path.to_str().is_some_and(object_store::is_object_uri) => {
+                Target::Object(path.to_str().unwrap().to_string(), Vec::new())
+            }
+            Some(path) => Target::File(File::create(path)?),
+            None => Target::Stdout(io::stdout().lock()),
+        };
+        let buffered = BufWriter::with_capacity(1 << 16, target);
+        Ok(Sink(match compression {
+            Compression::None => Encoder::Plain(buffered),
+            Compression::Gzip => {
+                Encoder::Gzip(GzEncoder::new(buffered, flate2::Compression::default()))
+            }
+            Compression::Zstd => Encoder::Zstd(zstd::Encoder::new(buffered, 0)?),
+        }))
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        let buffered = match self.0 {
+            Encoder::Plain(buffered) => buffered,
+            Encoder::Gzip(encoder) => encoder.finish()?,
+            Encoder::Zstd(encoder) => encoder.finish()?,
+        };
+        match buffered.into_inner().map_err(|e| e.into_error())? {
+            #[cfg(feature = "object-store")]
+            Target::Object(uri, body) => {
+                object_store::upload(&uri, &body).map_err(io::Error::other)
+            }
+            mut target => target.flush(),
+        }
+    }
+}
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Encoder::Plain(out) => out.write(buf),
+            Encoder::Gzip(out) => out.write(buf),
+            Encoder::Zstd(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Encoder::Plain(out) => out.flush(),
+            Encoder::Gzip(out) => out.flush(),
+            Encoder::Zstd(out) => out.flush(),
+        }
+    }
+}
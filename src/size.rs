@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::rib;
+
+#[derive(StructOpt, Debug)]
+pub struct SizeOpts {
+    /// Print CSV instead of aligned text
+    #[structopt(long)]
+    csv: bool,
+
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+pub type Ranges = Vec<(u128, u128)>;
+
+/// Announced address space of one ASN, overlapping announcements counted once.
+struct AddressSpace {
+    v4: u128,
+    v6: u128,
+}
+
+pub fn run(mrt_file: &Path, opts: SizeOpts) {
+    let mut ranges: HashMap<u32, (Ranges, Ranges)> = opts
+        .asns
+        .iter()
+        .map(|&asn| (asn, Default::default()))
+        .collect();
+    rib::for_each_route(mrt_file, |route| {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some((v4, v6)) = ranges.get_mut(&origin) {
+            if route.prefix.is_ipv4() {
+                v4.push(route.prefix.bounds());
+            } else {
+                v6.push(route.prefix.bounds());
+            }
+        }
+    });
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    if opts.csv {
+        writeln!(
+            out,
+            "asn,ipv4_addresses,ipv4_slash24,ipv6_addresses,ipv6_slash48"
+        )
+        .unwrap();
+    }
+    for asn in &opts.asns {
+        let (v4, v6) = ranges.remove(asn).unwrap_or_default();
+        let space = AddressSpace {
+            v4: merged_size(v4),
+            v6: merged_size(v6),
+        };
+        let slash24 = space.v4 as f64 / (1u128 << 8) as f64;
+        let slash48 = space.v6 as f64 / (1u128 << 80) as f64;
+        if opts.csv {
+            writeln!(
+                out,
+                "{},{},{:.2},{},{:.2}",
+                asn, space.v4, slash24, space.v6, slash48
+            )
+        } else {
+            writeln!(
+                out,
+                "AS{}\tipv4 {} ({:.2} /24)\tipv6 {} ({:.2} /48)",
+                asn, space.v4, slash24, space.v6, slash48
+            )
+        }
+        .unwrap();
+    }
+    out.flush().unwrap();
+}
+
+/// Number of addresses covered by the union of inclusive `[first, last]` ranges.
+pub fn merged_size(mut ranges: Ranges) -> u128 {
+    ranges.sort_unstable();
+    let mut total: u128 = 0;
+    let mut current: Option<(u128, u128)> = None;
+    for (first, last) in ranges {
+        current = match current {
+            Some((start, end)) if first <= end.saturating_add(1) => Some((start, end.max(last))),
+            Some((start, end)) => {
+                total = total.saturating_add(end - start).saturating_add(1);
+                Some((first, last))
+            }
+            None => Some((first, last)),
+        };
+    }
+    if let Some((start, end)) = current {
+        total = total.saturating_add(end - start).saturating_add(1);
+    }
+    total
+}
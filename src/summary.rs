@@ -0,0 +1,102 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::http_cache::{self, CacheStats};
+use crate::unmatched::Unmatched;
+
+/// Machine-readable account of a run, for capacity planning of scheduled builds.
+#[derive(Serialize, Debug, Default)]
+pub struct Summary {
+    pub sources: Vec<SourceStats>,
+    pub prefixes: usize,
+    /// Requested ASNs that originated no prefix.
+    pub unmatched: Vec<Unmatched>,
+    pub phases: Vec<Phase>,
+    /// Peak resident set size in KiB, where the platform reports it.
+    pub peak_rss_kib: Option<u64>,
+    pub cache: CacheStats,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SourceStats {
+    pub path: String,
+    pub routes: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Phase {
+    pub name: &'static str,
+    pub wall_ms: u128,
+    pub cpu_ms: Option<u128>,
+}
+
+/// Measures one phase of the run; hand it back to `Summary::end` when done.
+pub struct PhaseTimer {
+    name: &'static str,
+    wall: Instant,
+    cpu: Option<Duration>,
+}
+
+impl Summary {
+    pub fn begin(&self, name: &'static str) -> PhaseTimer {
+        PhaseTimer {
+            name,
+            wall: Instant::now(),
+            cpu: usage().map(|u| u.cpu),
+        }
+    }
+
+    pub fn end(&mut self, timer: PhaseTimer) {
+        let cpu = match (timer.cpu, usage()) {
+            (Some(before), Some(after)) => Some(after.cpu.saturating_sub(before).as_millis()),
+            _ => None,
+        };
+        self.phases.push(Phase {
+            name: timer.name,
+            wall_ms: timer.wall.elapsed().as_millis(),
+            cpu_ms: cpu,
+        });
+    }
+
+    pub fn write(mut self, path: &Path) -> io::Result<()> {
+        self.peak_rss_kib = usage().map(|u| u.max_rss_kib);
+        self.cache = http_cache::stats();
+        fs::write(path, serde_json::to_vec_pretty(&self)?)
+    }
+}
+
+struct Usage {
+    cpu: Duration,
+    max_rss_kib: u64,
+}
+
+#[cfg(unix)]
+fn usage() -> Option<Usage> {
+    let mut ru: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut ru) } != 0 {
+        return None;
+    }
+    let timeval = |tv: libc::timeval| {
+        Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+    };
+    // ru_maxrss is in KiB on Linux and the BSDs, but in bytes on macOS.
+    let max_rss = ru.ru_maxrss as u64;
+    let max_rss_kib = if cfg!(target_os = "macos") {
+        max_rss / 1024
+    } else {
+        max_rss
+    };
+    Some(Usage {
+        cpu: timeval(ru.ru_utime) + timeval(ru.ru_stime),
+        max_rss_kib,
+    })
+}
+
+#[cfg(not(unix))]
+fn usage() -> Option<Usage> {
+    None
+}
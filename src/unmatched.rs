@@ -0,0 +1,192 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::Serialize;
+
+use crate::rib::Route;
+
+/// Keeps enough about the table to explain why a requested ASN matched nothing.
+#[derive(Default)]
+pub struct MatchTracker {
+    matched: HashSet<u32>,
+    origins: HashSet<u32>,
+    /// Requested ASNs seen in a non-origin position, with the number of such paths.
+    upstream: HashMap<u32, usize>,
+    /// Requested ASNs originating routes dropped by --exclude-community, with
+    /// the number of such routes.
+    excluded: HashMap<u32, usize>,
+}
+
+impl MatchTracker {
+    /// Records `route`, which is `excluded` when a community filter drops it.
+    pub fn add(&mut self, route: &Route, asns: &HashSet<u32>, excluded: bool) {
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        self.origins.insert(origin);
+        if asns.contains(&origin) {
+            if excluded {
+                *self.excluded.entry(origin).or_default() += 1;
+            } else {
+                self.matched.insert(origin);
+            }
+        }
+        let mut transit: Vec<u32> = route.as_path[..route.as_path.len() - 1]
+            .iter()
+            .copied()
+            .filter(|asn| *asn != origin && asns.contains(asn))
+            .collect();
+        transit.sort_unstable();
+        transit.dedup();
+        for asn in transit {
+            *self.upstream.entry(asn).or_default() += 1;
+        }
+    }
+
+    /// Why the requested `asn` matched nothing.
+    fn hint(&self, asn: u32) -> String {
+        match (self.excluded.get(&asn), self.upstream.get(&asn)) {
+            (Some(routes), _) => {
+                format!("all {} routes dropped by --exclude-community", routes)
+            }
+            (None, Some(paths)) => format!("seen only as an upstream in {} paths", paths),
+            (None, None) => "not seen in the table".to_string(),
+        }
+    }
+
+    /// The requested ASNs that originated no prefix, ascending.
+    pub fn unmatched(&self, asns: &HashSet<u32>) -> Vec<Unmatched> {
+        let mut unmatched: Vec<u32> = asns.difference(&self.matched).copied().collect();
+        unmatched.sort_unstable();
+        (unmatched.into_iter())
+            .map(|asn| {
+                let mut suggestions: Vec<u32> = (self.origins.iter().copied())
+                    .filter(|origin| is_one_edit_apart(&asn.to_string(), &origin.to_string()))
+                    .collect();
+                suggestions.sort_unstable();
+                Unmatched {
+                    asn,
+                    reason: self.hint(asn),
+                    suggestions,
+                }
+            })
+            .collect()
+    }
+}
+
+/// A requested ASN that originated no prefix, with why and with originating
+/// ASNs one typo away.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Unmatched {
+    pub asn: u32,
+    pub reason: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<u32>,
+}
+
+/// Prints a warning to stderr for every unmatched ASN.
+pub fn warn(unmatched: &[Unmatched]) {
+    for u in unmatched {
+        if u.suggestions.is_empty() {
+            eprintln!("warning: AS{} matched no prefixes ({})", u.asn, u.reason);
+        } else {
+            let similar: Vec<String> = u.suggestions.iter().map(|a| format!("AS{}", a)).collect();
+            eprintln!(
+                "warning: AS{} matched no prefixes ({}); did you mean {}?",
+                u.asn,
+                u.reason,
+                similar.join(", ")
+            );
+        }
+    }
+}
+
+/// True if `b` can be reached from `a` by one substitution, insertion, deletion or
+/// swap of adjacent digits, the usual shapes of an ASN typo.
+fn is_one_edit_apart(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a == b {
+        return false;
+    }
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let (a, b) = (&a[prefix..], &b[prefix..]);
+    match a.len() as isize - b.len() as isize {
+        0 => a[1..] == b[1..] || (a.len() >= 2 && a[0] == b[1] && a[1] == b[0] && a[2..] == b[2..]),
+        1 => a[1..] == *b,
+        -1 => *a == b[1..],
+        _ => false,
+    }
+}
+
+/// Why `asn` is unlikely to be meant as a real network, if it is reserved.
+pub fn reserved_asn(asn: u32) -> Option<&'static str> {
+    match asn {
+        0 => Some("reserved, RFC 7607"),
+        23456 => Some("AS_TRANS, RFC 6793"),
+        64496..=64511 | 65536..=65551 => Some("documentation, RFC 5398"),
+        65535 | 4294967295 => Some("reserved, RFC 7300"),
+        _ => None,
+    }
+}
+
+/// Whether `asn` is from the private use ranges of RFC 6996, 16- or 32-bit.
+pub fn private_asn(asn: u32) -> bool {
+    matches!(asn, 64512..=65534 | 4200000000..=4294967294)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(path: &[u32]) -> Route {
+        Route {
+            prefix: "192.0.2.0/24".parse().unwrap(),
+            peer: None,
+            as_path: path.to_vec(),
+            communities: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn excluded_routes_are_seen_but_not_matched() {
+        let asns = HashSet::from([64500, 64501, 64502]);
+        let mut tracker = MatchTracker::default();
+        tracker.add(&route(&[64496, 64500]), &asns, true);
+        tracker.add(&route(&[64496, 64501]), &asns, false);
+        tracker.add(&route(&[64502, 64497]), &asns, false);
+        assert!(!tracker.matched.contains(&64500));
+        assert!(tracker.matched.contains(&64501));
+        assert_eq!(
+            tracker.hint(64500),
+            "all 1 routes dropped by --exclude-community"
+        );
+        assert_eq!(tracker.hint(64502), "seen only as an upstream in 1 paths");
+        assert_eq!(tracker.hint(64503), "not seen in the table");
+    }
+
+    #[test]
+    fn unmatched_suggests_typos() {
+        let asns = HashSet::from([13335, 64501]);
+        let mut tracker = MatchTracker::default();
+        tracker.add(&route(&[64496, 13355]), &asns, false);
+        tracker.add(&route(&[64496, 64501]), &asns, false);
+        assert_eq!(
+            tracker.unmatched(&asns),
+            [Unmatched {
+                asn: 13335,
+                reason: "not seen in the table".to_string(),
+                suggestions: vec![13355],
+            }]
+        );
+    }
+
+    #[test]
+    fn typos() {
+        assert!(is_one_edit_apart("13335", "13355"));
+        assert!(is_one_edit_apart("13335", "1335"));
+        assert!(is_one_edit_apart("13335", "133335"));
+        assert!(is_one_edit_apart("13335", "13353"));
+        assert!(!is_one_edit_apart("13335", "13335"));
+        assert!(!is_one_edit_apart("13335", "15533"));
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use structopt::StructOpt;
+
+use crate::output;
+use crate::prefix::Prefix;
+use crate::rib;
+
+#[derive(StructOpt, Debug)]
+pub struct VerifyOpts {
+    /// Pinned prefix list in plain format to compare against
+    #[structopt(long, parse(from_os_str))]
+    expected: PathBuf,
+
+    /// Number of added plus removed prefixes tolerated before failing
+    #[structopt(long, default_value = "0")]
+    tolerance: usize,
+
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+/// Recomputes the prefixes originated by the given ASNs and diffs them against
+/// a pinned list, exiting with status 1 when the difference exceeds the
+/// tolerance. Differences are printed as `+prefix`/`-prefix` lines either way.
+pub fn run(mrt_file: &Path, opts: VerifyOpts) {
+    let mut current: BTreeSet<Prefix> = BTreeSet::new();
+    rib::for_each_route(mrt_file, |route| {
+        if route.origin().is_some_and(|asn| opts.asns.contains(&asn)) {
+            current.insert(route.prefix);
+        }
+    });
+    let expected: BTreeSet<Prefix> = output::read_prefix_list(&opts.expected)
+        .into_iter()
+        .collect();
+
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    let mut changed = 0;
+    for prefix in expected.difference(&current) {
+        writeln!(out, "-{}", prefix).unwrap();
+        changed += 1;
+    }
+    for prefix in current.difference(&expected) {
+        writeln!(out, "+{}", prefix).unwrap();
+        changed += 1;
+    }
+    out.flush().unwrap();
+    if changed > opts.tolerance {
+        eprintln!(
+            "error: {} prefixes changed, tolerance is {}",
+            changed, opts.tolerance
+        );
+        process::exit(1);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Write};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::Path;
+
+use structopt::StructOpt;
+
+use crate::prefix::Prefix;
+use crate::rib::{self, Route};
+
+#[derive(StructOpt, Debug)]
+pub struct VisibilityOpts {
+    /// Report prefixes seen by less than this fraction of all peers
+    #[structopt(long, default_value = "0.5")]
+    min_visibility: f64,
+
+    #[structopt(required = true, min_values = 1)]
+    asns: Vec<u32>,
+}
+
+/// Peers are told apart by address and ASN, so the same session seen in dumps
+/// of several collectors counts once.
+pub type PeerKey = (IpAddr, u32);
+
+pub fn peer_key(route: &Route) -> Option<PeerKey> {
+    match route.peer {
+        Some(peer) => Some((peer.ip, peer.asn)),
+        None => route
+            .peer_asn()
+            .map(|asn| (IpAddr::V4(Ipv4Addr::UNSPECIFIED), asn)),
+    }
+}
+
+/// Reports how widely each ASN's prefixes propagate: how many of the dump's
+/// peers see any of them, how many prefixes every peer sees, and which prefixes
+/// fall below `--min-visibility`.
+pub fn run(mrt_file: &Path, opts: VisibilityOpts) {
+    let mut all_peers: HashSet<PeerKey> = HashSet::new();
+    let mut seen: HashMap<u32, BTreeMap<Prefix, HashSet<PeerKey>>> = opts
+        .asns
+        .iter()
+        .map(|&asn| (asn, BTreeMap::new()))
+        .collect();
+    rib::for_each_route(mrt_file, |route| {
+        let peer = match peer_key(route) {
+            Some(peer) => peer,
+            None => return,
+        };
+        all_peers.insert(peer);
+        let origin = match route.origin() {
+            Some(origin) => origin,
+            None => return,
+        };
+        if let Some(prefixes) = seen.get_mut(&origin) {
+            prefixes.entry(route.prefix).or_default().insert(peer);
+        }
+    });
+
+    let total = all_peers.len();
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    for asn in &opts.asns {
+        let prefixes = &seen[asn];
+        let peers: HashSet<&PeerKey> = prefixes.values().flatten().collect();
+        let full = prefixes.values().filter(|p| p.len() == total).count();
+        for (prefix, peers) in prefixes {
+            if (peers.len() as f64) < opts.min_visibility * total as f64 {
+                writeln!(
+                    out,
+                    "AS{}\tpoor\t{}\t{}/{}",
+                    asn,
+                    prefix,
+                    peers.len(),
+                    total
+                )
+                .unwrap();
+            }
+        }
+        writeln!(
+            out,
+            "# AS{}: seen by {}/{} peers, {} of {} prefixes seen by all ({:.1}%)",
+            asn,
+            peers.len(),
+            total,
+            full,
+            prefixes.len(),
+            if prefixes.is_empty() {
+                0.0
+            } else {
+                full as f64 * 100.0 / prefixes.len() as f64
+            }
+        )
+        .unwrap();
+    }
+    out.flush().unwrap();
+}